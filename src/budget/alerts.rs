@@ -0,0 +1,101 @@
+use chrono::{Local, NaiveDate};
+use rusqlite::Connection;
+use crate::models::budget::BudgetKind;
+use crate::output::Table;
+use super::status::budget_status;
+
+/// Checks every budget's current period against its thresholds and records
+/// the ones crossed for the first time. Returns only the newly fired alerts,
+/// so each threshold fires once per period.
+pub fn evaluate(conn: &Connection, today: NaiveDate) -> Table {
+    let budgets = crate::db::load_budgets(conn).unwrap();
+    let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let mut fired = alerts_table("new_alerts").with_title("Budget alerts:");
+
+    for budget in budgets.iter().filter(|b| b.kind == BudgetKind::Expense) {
+        let status = budget_status(conn, budget, today);
+
+        for threshold in budget.thresholds.iter().filter(|t| status.reached(**t)) {
+            let inserted = conn.execute(
+                "INSERT OR IGNORE INTO budget_alerts
+                    (category, period_start, period_end, threshold, spent, limit_amount, fired_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                (
+                    &budget.category,
+                    status.from.to_string(),
+                    status.to.to_string(),
+                    threshold,
+                    status.net,
+                    status.available(),
+                    &now,
+                ),
+            ).expect("Failed to record budget alert");
+
+            if inserted > 0 {
+                fired.push(vec![
+                    conn.last_insert_rowid().into(),
+                    budget.category.as_str().into(),
+                    status.from.to_string().into(),
+                    status.to.to_string().into(),
+                    (*threshold as i64).into(),
+                    status.net.into(),
+                    status.available().into(),
+                    now.as_str().into(),
+                    "no".into(),
+                ]);
+            }
+        }
+    }
+
+    fired
+}
+
+
+fn alerts_table(name: &str) -> Table {
+    Table::new(name, &["id", "category", "from", "to", "threshold_pct", "spent", "limit", "fired_at", "acknowledged"])
+}
+
+pub fn list_alerts(conn: &Connection, include_acknowledged: bool) -> Table {
+    let mut stmt = conn.prepare(
+        "SELECT id, category, period_start, period_end, threshold, spent, limit_amount, fired_at, acknowledged
+         FROM budget_alerts
+         WHERE acknowledged = 0 OR ?1
+         ORDER BY fired_at, id"
+    ).unwrap();
+
+    let rows = stmt.query_map([include_acknowledged], |row| {
+        Ok(vec![
+            row.get::<_, i64>(0)?.into(),
+            row.get::<_, String>(1)?.into(),
+            row.get::<_, String>(2)?.into(),
+            row.get::<_, String>(3)?.into(),
+            row.get::<_, i64>(4)?.into(),
+            row.get::<_, f64>(5)?.into(),
+            row.get::<_, f64>(6)?.into(),
+            row.get::<_, String>(7)?.into(),
+            if row.get::<_, bool>(8)? { "yes" } else { "no" }.into(),
+        ])
+    }).unwrap();
+
+    let mut table = alerts_table("alerts");
+    for row in rows.flatten() {
+        table.push(row);
+    }
+    table
+}
+
+/// Acknowledges the given alerts, or all of them when `ids` is empty.
+pub fn acknowledge(conn: &Connection, ids: &[i64]) -> usize {
+    if ids.is_empty() {
+        return conn
+            .execute("UPDATE budget_alerts SET acknowledged = 1 WHERE acknowledged = 0", [])
+            .expect("Failed to acknowledge alerts");
+    }
+
+    ids.iter()
+        .map(|id| {
+            conn.execute("UPDATE budget_alerts SET acknowledged = 1 WHERE id = ?1", [id])
+                .expect("Failed to acknowledge alert")
+        })
+        .sum()
+}
@@ -0,0 +1,224 @@
+use chrono::{Datelike, Days, Months, NaiveDate};
+use rusqlite::Connection;
+use crate::db::ISO_DATE;
+use crate::output::Table;
+
+/// An envelope's state for one month. Balances carry over between months, so
+/// `available` includes everything assigned and spent up to the month's end.
+pub struct Envelope {
+    pub category: String,
+    pub assigned: f64,
+    pub activity: f64,
+    pub available: f64,
+    /// The category's budget limit, if it has one
+    pub limit: Option<f64>,
+}
+
+
+pub fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).expect("first of month is valid")
+}
+
+fn month_end(month: NaiveDate) -> NaiveDate {
+    month_start(month) + Months::new(1) - Days::new(1)
+}
+
+fn month_key(month: NaiveDate) -> String {
+    month.format("%Y-%m").to_string()
+}
+
+/// Parses `YYYY-MM` or `MM/YYYY` into the first day of that month.
+pub fn parse_month(text: &str) -> Option<NaiveDate> {
+    let text = text.trim();
+    NaiveDate::parse_from_str(&format!("{}-01", text), "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(&format!("01/{}", text), "%d/%m/%Y"))
+        .ok()
+}
+
+
+pub fn assign(conn: &Connection, category: &str, amount: f64, month: NaiveDate) -> Result<(), String> {
+    if !amount.is_finite() {
+        return Err(format!("{} is not an amount that can be assigned.", amount));
+    }
+    conn.execute(
+        "INSERT INTO envelope_allocations (month, category, amount) VALUES (?1, ?2, ?3)",
+        (month_key(month), category, amount),
+    ).expect("Failed to assign money");
+    Ok(())
+}
+
+/// Moves `amount` from one envelope to another. The source must be an
+/// existing envelope; the target becomes one if it isn't yet.
+pub fn move_money(conn: &Connection, from: &str, to: &str, amount: f64, month: NaiveDate) -> Result<(), String> {
+    if amount <= 0.0 || !amount.is_finite() {
+        return Err(format!("The amount to move must be positive, not {}.", amount));
+    }
+    if !is_envelope(conn, from) {
+        return Err(format!("There is no '{}' envelope to move money from.", from));
+    }
+    assign(conn, from, -amount, month)?;
+    assign(conn, to, amount, month)
+}
+
+fn is_envelope(conn: &Connection, category: &str) -> bool {
+    conn.query_row(
+        &format!("SELECT EXISTS (SELECT 1 FROM ({}) WHERE category = ?1)", ENVELOPE_CATEGORIES),
+        [category],
+        |row| row.get(0),
+    ).unwrap()
+}
+
+
+// Envelopes are the categories that have a budget or have ever been assigned
+// money; positive transactions anywhere else count as income.
+const ENVELOPE_CATEGORIES: &str = "SELECT category FROM budgets WHERE kind = 'expense'
+	UNION SELECT category FROM envelope_allocations";
+
+// The first day envelopes were used: the first month money was assigned,
+// or `?1` (a `YYYY-MM` month) before anything has been.
+const ENVELOPES_START: &str = "COALESCE((SELECT MIN(month) FROM envelope_allocations), ?1) || '-01'";
+
+/// Income received since envelopes were first used, up to the end of
+/// `month`, minus everything assigned up to and including `month`. Like
+/// envelope activity, earlier income doesn't count.
+pub fn to_be_budgeted(conn: &Connection, month: NaiveDate) -> f64 {
+    let income: f64 = conn
+        .query_row(
+            &format!(
+                "SELECT COALESCE(SUM(amount), 0) FROM transactions
+                 WHERE amount > 0
+                 AND {date} BETWEEN {start} AND ?2
+                 AND COALESCE(category, '') NOT IN ({envelopes})",
+                date = ISO_DATE,
+                start = ENVELOPES_START,
+                envelopes = ENVELOPE_CATEGORIES
+            ),
+            [month_key(month), month_end(month).to_string()],
+            |row| row.get(0),
+        )
+        .unwrap();
+
+    let assigned: f64 = conn
+        .query_row(
+            "SELECT COALESCE(SUM(amount), 0) FROM envelope_allocations WHERE month <= ?1",
+            [month_key(month)],
+            |row| row.get(0),
+        )
+        .unwrap();
+
+    income - assigned
+}
+
+/// Every envelope for `month`. Activity before an envelope first received
+/// money (or, for one never assigned any, before envelopes were first used)
+/// doesn't count against it, so switching to envelopes starts from zero.
+pub fn envelopes(conn: &Connection, month: NaiveDate) -> Vec<Envelope> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT e.category,
+            (SELECT COALESCE(SUM(amount), 0) FROM envelope_allocations
+                WHERE category = e.category AND month = ?1),
+            (SELECT COALESCE(SUM(amount), 0) FROM transactions
+                WHERE category = e.category AND {date} BETWEEN MAX(?2, e.since) AND ?3),
+            (SELECT COALESCE(SUM(amount), 0) FROM envelope_allocations
+                WHERE category = e.category AND month <= ?1)
+            + (SELECT COALESCE(SUM(amount), 0) FROM transactions
+                WHERE category = e.category AND {date} BETWEEN e.since AND ?3),
+            (SELECT limit_amount FROM budgets WHERE category = e.category AND kind = 'expense')
+         FROM (
+            SELECT c.category, COALESCE(
+                (SELECT MIN(month) FROM envelope_allocations WHERE category = c.category) || '-01',
+                {start}
+            ) AS since
+            FROM ({envelopes}) c
+         ) e
+         ORDER BY e.category",
+        date = ISO_DATE,
+        start = ENVELOPES_START,
+        envelopes = ENVELOPE_CATEGORIES
+    )).unwrap();
+
+    let rows = stmt.query_map(
+        [month_key(month), month.to_string(), month_end(month).to_string()],
+        |row| {
+            Ok(Envelope {
+                category: row.get(0)?,
+                assigned: row.get(1)?,
+                activity: row.get(2)?,
+                available: row.get(3)?,
+                limit: row.get(4)?,
+            })
+        },
+    ).unwrap();
+
+    rows.flatten().collect()
+}
+
+
+pub fn envelopes_tables(conn: &Connection, month: NaiveDate) -> Vec<Table> {
+    let mut summary = Table::new("summary", &["month", "to_be_budgeted"]);
+    summary.push(vec![month_key(month).into(), to_be_budgeted(conn, month).into()]);
+
+    let mut table = Table::new("envelopes", &["category", "assigned", "activity", "available", "status"])
+        .with_title(format!("Envelopes for {}", month_key(month)));
+    for e in envelopes(conn, month) {
+        let status = if e.available < 0.0 { "⚠️ Overspent" } else { "" };
+        table.push(vec![
+            e.category.into(),
+            e.assigned.into(),
+            e.activity.into(),
+            e.available.into(),
+            status.into(),
+        ]);
+    }
+
+    vec![summary, table]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    fn month(y: i32, m: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, 1).unwrap()
+    }
+
+    fn add(conn: &Connection, amount: f64, category: &str, date: &str) {
+        db::add_transaction(conn, amount, category, "", Some(date.to_string()), None);
+    }
+
+    #[test]
+    fn history_before_envelopes_is_left_out() {
+        let conn = db::test_db();
+        // A year of income and spending before envelopes were used.
+        add(&conn, 5000.0, "Salary", "2024-01-10");
+        add(&conn, -900.0, "Food", "2024-01-20");
+        add(&conn, -3000.0, "Rent", "2024-02-01");
+
+        add(&conn, 4000.0, "Salary", "2024-03-10");
+        assign(&conn, "Food", 1000.0, month(2024, 3)).unwrap();
+        add(&conn, -250.0, "Food", "2024-03-15");
+
+        assert_eq!(to_be_budgeted(&conn, month(2024, 3)), 3000.0);
+        let food = &envelopes(&conn, month(2024, 3))[0];
+        assert_eq!((food.assigned, food.activity, food.available), (1000.0, -250.0, 750.0));
+    }
+
+    #[test]
+    fn nothing_is_budgeted_before_envelopes_are_used() {
+        let conn = db::test_db();
+        add(&conn, 5000.0, "Salary", "2024-01-10");
+        assert_eq!(to_be_budgeted(&conn, month(2024, 3)), 0.0);
+    }
+
+    #[test]
+    fn rejects_amounts_that_are_not_numbers() {
+        let conn = db::test_db();
+        assert!(assign(&conn, "Food", f64::NAN, month(2024, 3)).is_err());
+        assert!(assign(&conn, "Food", f64::INFINITY, month(2024, 3)).is_err());
+        assert!(move_money(&conn, "Food", "Fun", 10.0, month(2024, 3)).is_err());
+        assign(&conn, "Food", 100.0, month(2024, 3)).unwrap();
+        assert!(move_money(&conn, "Food", "Fun", f64::NAN, month(2024, 3)).is_err());
+        assert!(move_money(&conn, "Food", "Fun", 40.0, month(2024, 3)).is_ok());
+    }
+}
@@ -1,63 +1,426 @@
+pub mod alerts;
+pub mod envelope;
+pub mod pace;
+pub mod status;
+pub mod template;
+
+use chrono::{Datelike, Days, Local, Months, NaiveDate};
 use rusqlite::Connection;
+use crate::db::ISO_DATE;
+use crate::models::budget::{format_thresholds, Budget, BudgetKind, Period, Rollover};
+use crate::output::Table;
+pub use status::{BudgetError, BudgetStatus, IncomeStatus, SavingsStatus};
+
+/// Saves a budget. A changed limit is recorded in the budget history as
+/// effective from `effective_from`; earlier periods keep their old limit.
+pub fn set_budget(conn: &Connection, budget: &Budget, effective_from: NaiveDate) {
+    let previous = limit_on(conn, &budget.category, effective_from);
 
-pub fn set_budget(conn: &Connection, category: &str, limit: f64) {
     conn.execute(
-        "INSERT INTO budgets (category, limit_amount)
-         VALUES (?1, ?2)
-         ON CONFLICT(category) DO UPDATE SET limit_amount = excluded.limit_amount",
-        (category, limit),
+        "INSERT INTO budgets (category, limit_amount, period, start_date, end_date, rollover, rollover_cap, thresholds, kind)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+         ON CONFLICT(category) DO UPDATE SET
+             limit_amount = excluded.limit_amount,
+             period = excluded.period,
+             start_date = excluded.start_date,
+             end_date = excluded.end_date,
+             rollover = excluded.rollover,
+             rollover_cap = excluded.rollover_cap,
+             thresholds = excluded.thresholds,
+             kind = excluded.kind",
+        (
+            &budget.category,
+            budget.limit_amount,
+            budget.period.as_str(),
+            budget.start_date.map(|d| d.to_string()),
+            budget.end_date.map(|d| d.to_string()),
+            budget.rollover.as_str(),
+            budget.rollover_cap,
+            format_thresholds(&budget.thresholds),
+            budget.kind.as_str(),
+        ),
     ).expect("Failed to set budget");
-	
-	println!("Budget added: {} → {} lei", category, limit);
+
+    if previous != Some(budget.limit_amount) {
+        conn.execute(
+            "INSERT INTO budget_history (category, limit_amount, effective_from) VALUES (?1, ?2, ?3)",
+            (&budget.category, budget.limit_amount, effective_from.to_string()),
+        ).expect("Failed to record budget history");
+    }
 }
 
 
+/// The limit in force for `category` on `date`, from the budget history.
+/// Before the first recorded limit took effect, that first limit applies.
+pub fn limit_on(conn: &Connection, category: &str, date: NaiveDate) -> Option<f64> {
+    conn.query_row(
+        "SELECT limit_amount FROM budget_history
+         WHERE category = ?1
+         ORDER BY (effective_from IS NULL OR effective_from <= ?2) DESC,
+             CASE WHEN effective_from IS NULL OR effective_from <= ?2 THEN COALESCE(effective_from, '') END DESC,
+             effective_from, id DESC
+         LIMIT 1",
+        [category, &date.to_string()],
+        |row| row.get(0),
+    ).ok()
+}
 
-pub fn spent_this_month(conn: &Connection, category: &str) -> f64 {
+pub fn history_table(conn: &Connection, category: &str) -> Table {
     let mut stmt = conn.prepare(
-        "SELECT SUM(amount) FROM transactions
+        "SELECT effective_from, limit_amount, changed_at
+         FROM budget_history
          WHERE category = ?1
-         AND substr(date, 1, 2) = strftime('%m', 'now')
-         AND substr(date, -4) = strftime('%Y', 'now')"
+         ORDER BY COALESCE(effective_from, ''), id"
     ).unwrap();
 
-    stmt.query_row([category], |row| {
-        Ok(row.get::<_, Option<f64>>(0)?.unwrap_or(0.0).abs())
+    let rows = stmt.query_map([category], |row| {
+        Ok((row.get::<_, Option<String>>(0)?, row.get::<_, f64>(1)?, row.get::<_, Option<String>>(2)?))
+    }).unwrap();
+
+    // A blank effective date marks the limit that applied before any change.
+    let mut table = Table::new("history", &["effective_from", "limit", "changed_at"])
+        .with_title(format!("{} budget history", category));
+    for (from, limit, changed_at) in rows.flatten() {
+        table.push(vec![from.into(), limit.into(), changed_at.into()]);
+    }
+    table
+}
+
+
+// Recurring periods without an explicit start line up with calendar weeks
+// (Monday), months, quarters and years.
+fn default_anchor(period: Period) -> NaiveDate {
+    match period {
+        Period::Weekly => NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+        _ => NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(),
+    }
+}
+
+fn shift_months(date: NaiveDate, months: i64) -> NaiveDate {
+    if months >= 0 {
+        date.checked_add_months(Months::new(months as u32))
+    } else {
+        date.checked_sub_months(Months::new(months.unsigned_abs() as u32))
+    }
+    .expect("date out of range")
+}
+
+/// Returns the first and last day (inclusive) of the budget period that
+/// contains `today`. Custom budgets always cover their fixed range.
+pub fn period_window(budget: &Budget, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let anchor = budget.start_date.unwrap_or_else(|| default_anchor(budget.period));
+
+    let step = match budget.period {
+        Period::Custom => {
+            let end = budget.end_date.unwrap_or(today).max(anchor);
+            return (anchor, end);
+        }
+        Period::Weekly => {
+            let n = (today - anchor).num_days().div_euclid(7);
+            let start = anchor + chrono::Duration::days(n * 7);
+            return (start, start + Days::new(6));
+        }
+        Period::Monthly => 1,
+        Period::Quarterly => 3,
+        Period::Yearly => 12,
+    };
+
+    let months = (today.year() - anchor.year()) as i64 * 12 + today.month() as i64 - anchor.month() as i64;
+    let mut n = months.div_euclid(step);
+    if shift_months(anchor, n * step) > today {
+        n -= 1;
+    }
+
+    let start = shift_months(anchor, n * step);
+    let end = shift_months(anchor, (n + 1) * step) - Days::new(1);
+    (start, end)
+}
+
+
+/// Money spent (outflows) and refunded (inflows) in a category between two
+/// dates, both as positive amounts.
+pub fn spending_between(conn: &Connection, category: &str, from: NaiveDate, to: NaiveDate) -> (f64, f64) {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT COALESCE(SUM(CASE WHEN amount < 0 THEN -amount ELSE 0 END), 0),
+                COALESCE(SUM(CASE WHEN amount > 0 THEN amount ELSE 0 END), 0)
+         FROM transactions
+         WHERE category = ?1
+         AND {date} BETWEEN ?2 AND ?3",
+        date = ISO_DATE
+    )).unwrap();
+
+    stmt.query_row([category, &from.to_string(), &to.to_string()], |row| {
+        Ok((row.get(0)?, row.get(1)?))
     }).unwrap()
 }
 
+/// Spending after refunds in a category between two dates.
+pub fn spent_between(conn: &Connection, category: &str, from: NaiveDate, to: NaiveDate) -> f64 {
+    let (spent, refunds) = spending_between(conn, category, from, to);
+    spent - refunds
+}
 
-pub fn check_budget(conn: &Connection, category: &str) {
-    let mut stmt = conn.prepare(
-        "SELECT limit_amount FROM budgets WHERE category = ?1"
-    ).unwrap();
 
-    let limit: f64 = stmt.query_row([category], |row| row.get(0)).unwrap();
-    let spent = spent_this_month(conn, category);
+/// One period of a budget's history.
+pub struct LedgerRow {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Amount carried in from the previous period (negative for a deficit)
+    pub opening: f64,
+    pub limit: f64,
+    pub spent: f64,
+    pub refunds: f64,
+    /// Amount carried out into the next period
+    pub carried: f64,
+}
+
+impl LedgerRow {
+    pub fn available(&self) -> f64 {
+        self.opening + self.limit
+    }
 
-    println!("Category: {}", category);
-    println!("Spent: {} lei", spent);
-    println!("Limit: {} lei", limit);
+    /// Spending after refunds.
+    pub fn net(&self) -> f64 {
+        self.spent - self.refunds
+    }
+}
 
-    let percent = spent / limit;
+fn carry_over(budget: &Budget, balance: f64) -> f64 {
+    let carried = match budget.rollover {
+        Rollover::None => 0.0,
+        Rollover::Surplus => balance.max(0.0),
+        Rollover::SurplusAndDeficit => balance,
+    };
+    match budget.rollover_cap {
+        Some(cap) => carried.min(cap),
+        None => carried,
+    }
+}
 
-    if percent >= 1.0 {
-        println!("⚠️ ALERT: Budget exceeded!");
-    } else if percent >= 0.8 {
-        println!("Warning: You reached 80% of your budget.");
-    } else {
-        println!("✓ You are within the budget.");
+// The ledger starts at the explicit start date, or else at the first
+// transaction in the category, so carried balances include all history.
+fn ledger_start(conn: &Connection, budget: &Budget, today: NaiveDate) -> NaiveDate {
+    if let Some(start) = budget.start_date {
+        return start;
+    }
+
+    let first: Option<String> = conn
+        .query_row(
+            &format!("SELECT MIN({}) FROM transactions WHERE category = ?1", ISO_DATE),
+            [&budget.category],
+            |row| row.get(0),
+        )
+        .unwrap();
+
+    first
+        .as_deref()
+        .and_then(crate::parser::parse_date)
+        .map_or(today, |d| d.min(today))
+}
+
+/// Walks the budget period by period up to the one containing `today`,
+/// carrying balances forward according to the rollover setting. Without
+/// rollover every period opens at zero, but the history is still listed.
+pub fn budget_ledger(conn: &Connection, budget: &Budget, today: NaiveDate) -> Vec<LedgerRow> {
+    let mut rows = Vec::new();
+    let mut date = ledger_start(conn, budget, today);
+    let mut opening = 0.0;
+
+    loop {
+        let (from, to) = period_window(budget, date);
+        // Each period uses the limit that was in force on its first day.
+        let limit = limit_on(conn, &budget.category, from).unwrap_or(budget.limit_amount);
+        let (spent, refunds) = spending_between(conn, &budget.category, from, to);
+        let carried = carry_over(budget, opening + limit - (spent - refunds));
+
+        rows.push(LedgerRow { from, to, opening, limit, spent, refunds, carried });
+
+        if budget.period == Period::Custom || to >= today {
+            break;
+        }
+        opening = carried;
+        date = to + Days::new(1);
+    }
+
+    rows
+}
+
+pub fn ledger_table(conn: &Connection, category: &str) -> Result<Table, BudgetError> {
+    let budget = status::load_budget(conn, category)?;
+    let today = Local::now().date_naive();
+
+    let mut table = Table::new("ledger", &["from", "to", "opening", "limit", "available", "spent", "refunds", "carried"])
+        .with_title(format!(
+            "{} budget ledger ({}, rollover: {})",
+            budget.category,
+            budget.period.as_str(),
+            budget.rollover.as_str()
+        ));
+
+    for row in budget_ledger(conn, &budget, today) {
+        table.push(vec![
+            row.from.to_string().into(),
+            row.to.to_string().into(),
+            row.opening.into(),
+            row.limit.into(),
+            row.available().into(),
+            row.spent.into(),
+            row.refunds.into(),
+            row.carried.into(),
+        ]);
+    }
+    Ok(table)
+}
+
+
+fn budget_table() -> Table {
+    Table::new("budgets", &[
+        "category", "period", "from", "to", "carried_in", "limit", "spent", "refunds", "net",
+        "expected", "projected", "safe_per_day", "status",
+    ])
+}
+
+fn push_status(table: &mut Table, status: &BudgetStatus) {
+    table.push(vec![
+        status.category.as_str().into(),
+        status.period.as_str().into(),
+        status.from.to_string().into(),
+        status.to.to_string().into(),
+        status.carried_in.into(),
+        status.limit.into(),
+        status.spent.into(),
+        status.refunds.into(),
+        status.net.into(),
+        status.pace.expected_to_date.into(),
+        status.pace.projected.into(),
+        status.pace.safe_per_day.into(),
+        status.state.message().into(),
+    ]);
+}
+
+
+fn income_table() -> Table {
+    Table::new("income_targets", &["category", "period", "from", "to", "expected", "received", "difference", "status"])
+        .with_title("Income targets")
+}
+
+fn push_income(table: &mut Table, status: &IncomeStatus) {
+    table.push(vec![
+        status.category.as_str().into(),
+        status.period.as_str().into(),
+        status.from.to_string().into(),
+        status.to.to_string().into(),
+        status.expected.into(),
+        status.received.into(),
+        (status.received - status.expected).into(),
+        status.message().into(),
+    ]);
+}
+
+fn savings_table() -> Table {
+    Table::new("savings", &[
+        "name", "period", "from", "to", "income", "expenses", "saved", "savings_rate", "target_rate", "status",
+    ]).with_title("Savings goals")
+}
+
+fn push_savings(table: &mut Table, status: &SavingsStatus) {
+    table.push(vec![
+        status.name.as_str().into(),
+        status.period.as_str().into(),
+        status.from.to_string().into(),
+        status.to.to_string().into(),
+        status.income.into(),
+        status.expenses.into(),
+        status.saved().into(),
+        status.rate().into(),
+        status.target.into(),
+        status.message().into(),
+    ]);
+}
+
+
+/// Evaluates a budget over the period containing `as_of`.
+pub fn check_budget(conn: &Connection, category: &str, as_of: NaiveDate) -> Result<Table, BudgetError> {
+    let budget = status::load_budget(conn, category)?;
+    let table = match budget.kind {
+        BudgetKind::Expense => {
+            let mut table = budget_table();
+            push_status(&mut table, &status::budget_status(conn, &budget, as_of));
+            table
+        }
+        BudgetKind::Income => {
+            let mut table = income_table();
+            push_income(&mut table, &status::income_status(conn, &budget, as_of));
+            table
+        }
+        BudgetKind::Savings => {
+            let mut table = savings_table();
+            push_savings(&mut table, &status::savings_status(conn, &budget, as_of));
+            table
+        }
+    };
+    Ok(table)
+}
+
+/// Evaluates all expense budgets.
+pub fn check_all(conn: &Connection, as_of: NaiveDate) -> Result<Table, BudgetError> {
+    let mut table = budget_table();
+    for status in status::all_statuses(conn, as_of)? {
+        push_status(&mut table, &status);
+    }
+    Ok(table)
+}
+
+/// Income targets and savings goals, one table each for those that exist.
+pub fn check_targets(conn: &Connection, as_of: NaiveDate) -> Result<Vec<Table>, BudgetError> {
+    let (incomes, goals) = status::target_statuses(conn, as_of)?;
+    let mut income = income_table();
+    for status in &incomes {
+        push_income(&mut income, status);
+    }
+    let mut savings = savings_table();
+    for status in &goals {
+        push_savings(&mut savings, status);
     }
+
+    Ok([income, savings].into_iter().filter(|t| !t.rows.is_empty()).collect())
 }
 
-pub fn check_all(conn: &Connection) {
-    let mut stmt = conn.prepare("SELECT category FROM budgets").unwrap();
-    let rows = stmt.query_map([], |row| row.get::<_, String>(0)).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
 
-    for cat in rows {
-        if let Ok(category) = cat {
-            check_budget(conn, &category);
-            println!("----------------------");
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn food(limit: f64) -> Budget {
+        Budget {
+            category: "Food".to_string(),
+            limit_amount: limit,
+            kind: BudgetKind::Expense,
+            period: Period::Monthly,
+            start_date: Some(date(2024, 1, 1)),
+            end_date: None,
+            rollover: Rollover::Surplus,
+            rollover_cap: None,
+            thresholds: crate::models::budget::DEFAULT_THRESHOLDS.to_vec(),
         }
     }
+
+    #[test]
+    fn periods_before_the_first_limit_use_it_not_the_current_one() {
+        let conn = db::test_db();
+        // Created in March for a budget starting in January, raised in May.
+        set_budget(&conn, &food(1000.0), date(2024, 3, 1));
+        set_budget(&conn, &food(1200.0), date(2024, 5, 1));
+        db::add_transaction(&conn, -900.0, "Food", "", Some("2024-01-10".to_string()), None);
+
+        let rows = budget_ledger(&conn, &food(1200.0), date(2024, 5, 15));
+        let limits: Vec<f64> = rows.iter().map(|r| r.limit).collect();
+        assert_eq!(limits, vec![1000.0, 1000.0, 1000.0, 1000.0, 1200.0]);
+        assert_eq!(rows[0].carried, 100.0);
+        assert_eq!(rows[4].opening, 3100.0);
+    }
 }
@@ -0,0 +1,91 @@
+use chrono::{Days, NaiveDate};
+use rusqlite::Connection;
+use crate::models::budget::Budget;
+use super::{period_window, spent_between, LedgerRow};
+
+// How many earlier periods are used to learn when in a period money is spent.
+const HISTORY_PERIODS: usize = 6;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Pace {
+    pub elapsed_days: i64,
+    pub total_days: i64,
+    /// What spending "on pace" would be by now
+    pub expected_to_date: f64,
+    /// Projected spending by the end of the period
+    pub projected: f64,
+    /// What can still be spent per remaining day without going over
+    pub safe_per_day: f64,
+}
+
+
+/// Average amount spent in the remainder of earlier periods, after the same
+/// fraction of the period had elapsed. `None` without any history.
+fn historical_remainder(conn: &Connection, budget: &Budget, current: &LedgerRow, fraction: f64) -> Option<f64> {
+    let mut remainders = Vec::new();
+    let mut date = current.from.checked_sub_days(Days::new(1))?;
+
+    for _ in 0..HISTORY_PERIODS {
+        let (from, to) = period_window(budget, date);
+        if to >= current.from {
+            break;
+        }
+
+        let length = (to - from).num_days() + 1;
+        let cutoff = from + Days::new(((length as f64 * fraction).round() as u64).max(1) - 1);
+        let total = spent_between(conn, &budget.category, from, to);
+        if total != 0.0 {
+            remainders.push(spent_between(conn, &budget.category, cutoff + Days::new(1), to));
+        }
+
+        date = match from.checked_sub_days(Days::new(1)) {
+            Some(d) => d,
+            None => break,
+        };
+    }
+
+    if remainders.is_empty() {
+        None
+    } else {
+        Some(remainders.iter().sum::<f64>() / remainders.len() as f64)
+    }
+}
+
+/// Pace analysis for the period in `current`, as of `today`.
+///
+/// The projection is the current run rate extrapolated to the end of the
+/// period, averaged with the spending-to-date plus what earlier periods
+/// typically spent in their remaining days.
+pub fn budget_pace(conn: &Connection, budget: &Budget, current: &LedgerRow, today: NaiveDate) -> Pace {
+    let total_days = (current.to - current.from).num_days() + 1;
+    let elapsed_days = ((today.min(current.to) - current.from).num_days() + 1).clamp(0, total_days);
+    let remaining_days = total_days - elapsed_days;
+    let fraction = elapsed_days as f64 / total_days as f64;
+
+    let available = current.available();
+    let expected_to_date = available * fraction;
+
+    let run_rate = if elapsed_days > 0 {
+        current.net() / elapsed_days as f64 * total_days as f64
+    } else {
+        0.0
+    };
+    let projected = if remaining_days == 0 {
+        current.net()
+    } else {
+        match historical_remainder(conn, budget, current, fraction) {
+            Some(remainder) => (run_rate + current.net() + remainder) / 2.0,
+            None => run_rate,
+        }
+    };
+
+    // Today still counts as a day left to spend in.
+    let days_left = ((current.to - today.max(current.from)).num_days() + 1).max(0);
+    let safe_per_day = if days_left > 0 {
+        (available - current.net()).max(0.0) / days_left as f64
+    } else {
+        0.0
+    };
+
+    Pace { elapsed_days, total_days, expected_to_date, projected, safe_per_day }
+}
@@ -0,0 +1,259 @@
+use std::fmt;
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use crate::models::budget::{Budget, BudgetKind, Period};
+use crate::reports::range::DateRange;
+use crate::reports::{category_totals, Totals};
+use super::pace::{budget_pace, Pace};
+use super::{budget_ledger, period_window, spending_between, LedgerRow};
+
+#[derive(Debug)]
+pub enum BudgetError {
+    /// The category has no budget
+    NotFound(String),
+    Database(rusqlite::Error),
+}
+
+impl fmt::Display for BudgetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BudgetError::NotFound(category) => write!(f, "No budget set for category '{}'.", category),
+            BudgetError::Database(err) => write!(f, "Failed to load budgets: {}", err),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for BudgetError {
+    fn from(err: rusqlite::Error) -> Self {
+        BudgetError::Database(err)
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetState {
+    Within,
+    /// No threshold reached yet, but projected to overspend by this much
+    OnPaceToOverspend(f64),
+    /// The highest threshold reached, below 100%
+    Warning(u32),
+    Exceeded,
+}
+
+impl BudgetState {
+    pub fn message(&self) -> String {
+        match self {
+            BudgetState::Within => "✓ You are within the budget.".to_string(),
+            BudgetState::OnPaceToOverspend(over) => format!("Warning: On pace to overspend by {:.2} lei.", over),
+            BudgetState::Warning(t) => format!("Warning: You reached {}% of your budget.", t),
+            BudgetState::Exceeded => "⚠️ ALERT: Budget exceeded!".to_string(),
+        }
+    }
+}
+
+/// A budget evaluated over one period.
+pub struct BudgetStatus {
+    pub category: String,
+    pub period: Period,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// Amount carried in from the previous period (negative for a deficit)
+    pub carried_in: f64,
+    pub limit: f64,
+    /// Money spent in the category (outflows only)
+    pub spent: f64,
+    /// Refunds and other money received in the category
+    pub refunds: f64,
+    /// Spending after refunds; this is what counts against the budget
+    pub net: f64,
+    /// Share of the available amount used, `None` when nothing is available
+    pub percent: Option<f64>,
+    pub pace: Pace,
+    pub state: BudgetState,
+}
+
+impl BudgetStatus {
+    pub fn available(&self) -> f64 {
+        self.carried_in + self.limit
+    }
+
+    pub fn remaining(&self) -> f64 {
+        self.available() - self.net
+    }
+
+    /// Whether spending has reached `threshold` percent of the available
+    /// amount. Any spending reaches every threshold of an empty budget.
+    pub fn reached(&self, threshold: u32) -> bool {
+        match self.percent {
+            Some(percent) => percent >= threshold as f64,
+            None => self.net > 0.0,
+        }
+    }
+}
+
+/// Evaluates a budget's period. Pure: everything it needs is passed in.
+pub fn evaluate(budget: &Budget, current: &LedgerRow, pace: Pace) -> BudgetStatus {
+    let available = current.available();
+    let net = current.net();
+    let percent = (available > 0.0).then(|| net.max(0.0) / available * 100.0);
+
+    let mut status = BudgetStatus {
+        category: budget.category.clone(),
+        period: budget.period,
+        from: current.from,
+        to: current.to,
+        carried_in: current.opening,
+        limit: current.limit,
+        spent: current.spent,
+        refunds: current.refunds,
+        net,
+        percent,
+        pace,
+        state: BudgetState::Within,
+    };
+
+    let reached = budget.thresholds.iter().rev().find(|t| status.reached(**t));
+    status.state = match reached {
+        _ if net > available => BudgetState::Exceeded,
+        Some(t) if *t >= 100 => BudgetState::Exceeded,
+        Some(t) => BudgetState::Warning(*t),
+        None if status.pace.projected > available => {
+            BudgetState::OnPaceToOverspend(status.pace.projected - available)
+        }
+        None => BudgetState::Within,
+    };
+    status
+}
+
+/// Evaluates `budget` over the period containing `as_of`.
+pub fn budget_status(conn: &Connection, budget: &Budget, as_of: NaiveDate) -> BudgetStatus {
+    let current = budget_ledger(conn, budget, as_of).pop().expect("ledger has a current period");
+    let pace = budget_pace(conn, budget, &current, as_of);
+    evaluate(budget, &current, pace)
+}
+
+pub fn load_budget(conn: &Connection, category: &str) -> Result<Budget, BudgetError> {
+    crate::db::load_budget(conn, category).map_err(|err| match err {
+        rusqlite::Error::QueryReturnedNoRows => BudgetError::NotFound(category.to_string()),
+        other => BudgetError::Database(other),
+    })
+}
+
+/// Statuses of all expense budgets.
+pub fn all_statuses(conn: &Connection, as_of: NaiveDate) -> Result<Vec<BudgetStatus>, BudgetError> {
+    let budgets = crate::db::load_budgets(conn)?;
+    Ok(budgets
+        .iter()
+        .filter(|b| b.kind == BudgetKind::Expense)
+        .map(|b| budget_status(conn, b, as_of))
+        .collect())
+}
+
+
+/// Expected against actual income in a category over one period.
+pub struct IncomeStatus {
+    pub category: String,
+    pub period: Period,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub expected: f64,
+    /// Income received, less any outflows in the category
+    pub received: f64,
+}
+
+impl IncomeStatus {
+    pub fn shortfall(&self) -> f64 {
+        (self.expected - self.received).max(0.0)
+    }
+
+    pub fn message(&self) -> String {
+        if self.shortfall() > 0.0 {
+            format!("Expecting {:.2} lei more.", self.shortfall())
+        } else {
+            "✓ Expected income received.".to_string()
+        }
+    }
+}
+
+pub fn income_status(conn: &Connection, budget: &Budget, as_of: NaiveDate) -> IncomeStatus {
+    let (from, to) = period_window(budget, as_of);
+    let (outflows, inflows) = spending_between(conn, &budget.category, from, to);
+
+    IncomeStatus {
+        category: budget.category.clone(),
+        period: budget.period,
+        from,
+        to,
+        expected: budget.limit_amount,
+        received: inflows - outflows,
+    }
+}
+
+
+/// The savings rate over one period against a target.
+pub struct SavingsStatus {
+    pub name: String,
+    pub period: Period,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub income: f64,
+    /// Spending after refunds
+    pub expenses: f64,
+    /// Target savings rate in percent
+    pub target: f64,
+}
+
+impl SavingsStatus {
+    pub fn saved(&self) -> f64 {
+        self.income - self.expenses
+    }
+
+    /// Saved share of income in percent, `None` without income.
+    pub fn rate(&self) -> Option<f64> {
+        (self.income > 0.0).then(|| self.saved() / self.income * 100.0)
+    }
+
+    pub fn message(&self) -> String {
+        match self.rate() {
+            None => "No income yet.".to_string(),
+            Some(rate) if rate >= self.target => "✓ On target.".to_string(),
+            Some(rate) => format!(
+                "Below target by {:.1} points ({:.2} lei).",
+                self.target - rate,
+                self.income * self.target / 100.0 - self.saved()
+            ),
+        }
+    }
+}
+
+// Income and expenses are counted the same way as in reports.
+pub fn savings_status(conn: &Connection, budget: &Budget, as_of: NaiveDate) -> SavingsStatus {
+    let (from, to) = period_window(budget, as_of);
+    let totals = Totals::from_categories(&category_totals(conn, DateRange { from, to }));
+
+    SavingsStatus {
+        name: budget.category.clone(),
+        period: budget.period,
+        from,
+        to,
+        income: totals.income,
+        expenses: totals.expenses,
+        target: budget.limit_amount,
+    }
+}
+
+/// Statuses of all income targets and savings goals.
+pub fn target_statuses(conn: &Connection, as_of: NaiveDate) -> Result<(Vec<IncomeStatus>, Vec<SavingsStatus>), BudgetError> {
+    let budgets = crate::db::load_budgets(conn)?;
+    let mut income = Vec::new();
+    let mut savings = Vec::new();
+
+    for budget in &budgets {
+        match budget.kind {
+            BudgetKind::Expense => {}
+            BudgetKind::Income => income.push(income_status(conn, budget, as_of)),
+            BudgetKind::Savings => savings.push(savings_status(conn, budget, as_of)),
+        }
+    }
+    Ok((income, savings))
+}
@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use chrono::{Days, NaiveDate};
+use rusqlite::Connection;
+use crate::db::ISO_DATE;
+use crate::models::budget::{format_thresholds, Budget, BudgetKind, Period, Rollover, SuggestMethod, DEFAULT_THRESHOLDS};
+use crate::output::Table;
+use crate::stats::median;
+use super::envelope::month_start;
+use super::{period_window, set_budget, shift_months};
+
+/// A suggested limit for one category, next to its current budget.
+pub struct Suggestion {
+    pub category: String,
+    /// The category's current budget, if it has one
+    pub current: Option<Budget>,
+    pub average: f64,
+    pub median: f64,
+    /// Suggested limit for the budget's period (monthly for new budgets)
+    pub suggested: Option<f64>,
+}
+
+
+/// Monthly spending per category over the `months` full months before
+/// `today`'s month. Months without spending count as zero.
+fn monthly_spending(conn: &Connection, months: u32, today: NaiveDate) -> BTreeMap<String, Vec<f64>> {
+    let to = month_start(today);
+    let from = shift_months(to, -(months as i64));
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT category, substr({date}, 1, 7) AS month, -SUM(amount)
+         FROM transactions
+         WHERE category IS NOT NULL AND category <> ''
+         AND {date} >= ?1 AND {date} < ?2
+         GROUP BY category, month
+         HAVING SUM(CASE WHEN amount < 0 THEN 1 ELSE 0 END) > 0",
+        date = ISO_DATE
+    )).unwrap();
+
+    let rows = stmt.query_map([from.to_string(), to.to_string()], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, f64>(2)?))
+    }).unwrap();
+
+    let mut spending: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    for (category, spent) in rows.flatten() {
+        spending.entry(category).or_default().push(spent.max(0.0));
+    }
+    for values in spending.values_mut() {
+        values.resize(months as usize, 0.0);
+        values.sort_by(|a, b| a.total_cmp(b));
+    }
+    spending
+}
+
+fn average(sorted: &[f64]) -> f64 {
+    sorted.iter().sum::<f64>() / sorted.len() as f64
+}
+
+// Nearest-rank percentile.
+fn percentile(sorted: &[f64], p: u32) -> f64 {
+    let rank = (p as f64 / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// How many months a period covers; custom ranges have no monthly equivalent.
+fn months_per_period(period: Period) -> Option<f64> {
+    match period {
+        Period::Weekly => Some(12.0 / 52.0),
+        Period::Monthly => Some(1.0),
+        Period::Quarterly => Some(3.0),
+        Period::Yearly => Some(12.0),
+        Period::Custom => None,
+    }
+}
+
+/// Suggests limits for every category with spending in the last `months`
+/// full months. Suggestions are rounded up to whole tens of lei and scaled
+/// to the period of an existing budget.
+pub fn suggest(conn: &Connection, months: u32, method: SuggestMethod, pct: u32, today: NaiveDate) -> Vec<Suggestion> {
+    monthly_spending(conn, months.max(1), today)
+        .into_iter()
+        .filter_map(|(category, sorted)| {
+            let current = crate::db::load_budget(conn, &category).ok();
+            // Income and savings targets aren't spending limits.
+            if current.as_ref().is_some_and(|b| b.kind != BudgetKind::Expense) {
+                return None;
+            }
+
+            let monthly = match method {
+                SuggestMethod::Median => median(&sorted),
+                SuggestMethod::Average => average(&sorted),
+                SuggestMethod::Percentile => percentile(&sorted, pct),
+            };
+            let factor = current.as_ref().map_or(Some(1.0), |b| months_per_period(b.period));
+            let suggested = factor.map(|f| (monthly * f / 10.0).ceil() * 10.0);
+
+            Some(Suggestion { category, current, average: average(&sorted), median: median(&sorted), suggested })
+        })
+        .collect()
+}
+
+pub fn suggestions_table(suggestions: &[Suggestion], title: String) -> Table {
+    let mut table = Table::new("suggestions", &[
+        "category", "period", "current_limit", "average", "median", "suggested", "change",
+    ]).with_title(title);
+
+    for s in suggestions {
+        let current = s.current.as_ref().map(|b| b.limit_amount);
+        let period = s.current.as_ref().map_or(Period::Monthly, |b| b.period);
+        let change = match (current, s.suggested) {
+            (Some(c), Some(n)) => Some(n - c),
+            _ => None,
+        };
+        table.push(vec![
+            s.category.as_str().into(),
+            period.as_str().into(),
+            current.into(),
+            s.average.into(),
+            s.median.into(),
+            s.suggested.into(),
+            change.into(),
+        ]);
+    }
+    table
+}
+
+/// The budgets the suggestions would produce: existing budgets keep all
+/// their settings except the limit, new ones are monthly. Categories
+/// without a positive suggestion are left out.
+pub fn suggested_budgets(suggestions: Vec<Suggestion>) -> Vec<Budget> {
+    suggestions
+        .into_iter()
+        .filter_map(|s| {
+            let limit = s.suggested.filter(|l| *l > 0.0)?;
+            Some(match s.current {
+                Some(budget) => Budget { limit_amount: limit, ..budget },
+                None => Budget {
+                    category: s.category,
+                    limit_amount: limit,
+                    kind: BudgetKind::Expense,
+                    period: Period::Monthly,
+                    start_date: None,
+                    end_date: None,
+                    rollover: Rollover::None,
+                    rollover_cap: None,
+                    thresholds: DEFAULT_THRESHOLDS.to_vec(),
+                },
+            })
+        })
+        .collect()
+}
+
+
+/// Saves each budget, with changed limits taking effect from the start of
+/// the current period.
+pub fn apply_budgets(conn: &Connection, budgets: &[Budget], today: NaiveDate) {
+    for budget in budgets {
+        set_budget(conn, budget, period_window(budget, today).0);
+    }
+}
+
+/// Stores `budgets` as the template `name`, replacing any earlier version.
+pub fn save_template(conn: &Connection, name: &str, budgets: &[Budget]) {
+    conn.execute("DELETE FROM budget_templates WHERE name = ?1", [name])
+        .expect("Failed to save template");
+
+    for budget in budgets {
+        conn.execute(
+            "INSERT INTO budget_templates
+                (name, category, limit_amount, period, start_date, end_date, rollover, rollover_cap, thresholds, kind)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            (
+                name,
+                &budget.category,
+                budget.limit_amount,
+                budget.period.as_str(),
+                budget.start_date.map(|d| d.to_string()),
+                budget.end_date.map(|d| d.to_string()),
+                budget.rollover.as_str(),
+                budget.rollover_cap,
+                format_thresholds(&budget.thresholds),
+                budget.kind.as_str(),
+            ),
+        ).expect("Failed to save template");
+    }
+}
+
+/// Restores the budgets in template `name`. With `replace`, budgets that
+/// aren't in the template are removed. Returns `None` for an unknown template.
+pub fn apply_template(conn: &Connection, name: &str, replace: bool, today: NaiveDate) -> Option<usize> {
+    let budgets = crate::db::load_template(conn, name).unwrap();
+    if budgets.is_empty() {
+        return None;
+    }
+
+    if replace {
+        conn.execute(
+            "DELETE FROM budgets WHERE category NOT IN
+                (SELECT category FROM budget_templates WHERE name = ?1)",
+            [name],
+        ).expect("Failed to remove budgets");
+    }
+    apply_budgets(conn, &budgets, today);
+    Some(budgets.len())
+}
+
+pub fn templates_table(conn: &Connection) -> Table {
+    let mut stmt = conn.prepare(
+        "SELECT name, COUNT(*), SUM(limit_amount) FROM budget_templates GROUP BY name ORDER BY name"
+    ).unwrap();
+
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, f64>(2)?))
+    }).unwrap();
+
+    let mut table = Table::new("templates", &["name", "budgets", "total_limit"]);
+    for (name, count, total) in rows.flatten() {
+        table.push(vec![name.into(), count.into(), total.into()]);
+    }
+    table
+}
+
+/// The date range suggestions are based on, for titles.
+pub fn window_label(months: u32, today: NaiveDate) -> String {
+    let to = month_start(today) - Days::new(1);
+    let from = shift_months(month_start(today), -(months.max(1) as i64));
+    format!("{} – {}", from, to)
+}
@@ -13,7 +13,7 @@ fn get_rules() -> Vec<(Regex, &'static str)> {
 }
 
 
-pub fn categorize_transactions(conn: &Connection) {
+pub fn categorize_transactions(conn: &Connection) -> usize {
 
     let mut stmt = conn
         .prepare(
@@ -34,24 +34,22 @@ pub fn categorize_transactions(conn: &Connection) {
     let rules = get_rules();
     let mut updated = 0;
 
-    for result in rows {
-        if let Ok((id, description)) = result {
-            let desc_lower = description.to_lowercase();
-
-            for (regex, new_category) in &rules {
-                if regex.is_match(&desc_lower) {
-                    conn.execute(
-                        "UPDATE transactions SET category = ?1 WHERE id = ?2",
-                        (new_category, id),
-                    )
-                    .expect("Failed to update category");
-
-                    updated += 1;
-                    break;
-                }
+    for (id, description) in rows.flatten() {
+        let desc_lower = description.to_lowercase();
+
+        for (regex, new_category) in &rules {
+            if regex.is_match(&desc_lower) {
+                conn.execute(
+                    "UPDATE transactions SET category = ?1 WHERE id = ?2",
+                    (new_category, id),
+                )
+                .expect("Failed to update category");
+
+                updated += 1;
+                break;
             }
         }
     }
 
-    println!("Categorization complete. {} transactions updated.", updated);
+    updated
 }
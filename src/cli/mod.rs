@@ -1,4 +1,10 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use crate::models::account::AccountKind;
+use crate::models::budget::{BudgetKind, Period, Rollover, SuggestMethod};
+use crate::output::Format;
+use crate::reports::pivot::{Aggregate, Dimension};
+use crate::reports::range::RangeSpec;
+use crate::reports::series::GroupBy;
 
 #[derive(Parser)]
 #[command(name = "finance-cli")]
@@ -6,6 +12,10 @@ use clap::{Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Output format for command results
+    #[arg(long, global = true, value_enum, default_value_t = Format::Table)]
+    pub format: Format,
 }
 
 #[derive(Subcommand)]
@@ -19,34 +29,374 @@ pub enum Commands {
 		description: String,
         #[arg(short = 'D', long)]
         date: Option<String>,
+        /// Account the transaction belongs to (default: the default account)
+        #[arg(long)]
+        account: Option<String>,
+        /// Free-form notes, found by `search`
+        #[arg(short, long)]
+        notes: Option<String>,
     },
 
     Import {
         #[arg(short, long)]
         file: String,
+        /// csv, ledger (hledger) or beancount
         #[arg(short, long, default_value = "csv")]
         r#type: String,
+        /// Account the transactions belong to (default: the default account)
+        #[arg(long)]
+        account: Option<String>,
+        /// Check the imported transactions for unusual amounts
+        #[arg(long)]
+        anomalies: bool,
     },
 
+    #[command(args_conflicts_with_subcommands = true)]
     Reports {
-    #[arg(long)]
-    month: Option<String>,   
-    #[arg(long)]
-    year: Option<String>,   
+        #[command(subcommand)]
+        action: Option<ReportAction>,
+        #[command(flatten)]
+        range: RangeArgs,
+        /// Add a time series of income, expenses and net per bucket
+        #[arg(short, long, value_enum)]
+        group_by: Option<GroupBy>,
+        /// Add top payees, the largest expenses, ticket sizes and spending by
+        /// weekday and day of month, ranking N rows
+        #[arg(short, long, value_name = "N")]
+        top: Option<usize>,
     },
 
+    #[command(args_conflicts_with_subcommands = true)]
     Budget {
+        #[command(subcommand)]
+        action: Option<BudgetAction>,
         #[arg(short, long)]
         category: Option<String>,
+        /// Spending limit, expected income, or target savings rate in percent
         #[arg(short, long)]
         limit: Option<f64>,
+        /// What the budget tracks (default: expense). Options left out keep
+        /// their current setting when changing an existing budget
+        #[arg(short, long, value_enum, requires = "limit")]
+        kind: Option<BudgetKind>,
+        /// How often the limit resets (default: monthly)
+        #[arg(short, long, value_enum, requires = "limit")]
+        period: Option<Period>,
+        /// First day of the budget (MM/DD/YYYY or YYYY-MM-DD); anchors recurring periods
+        #[arg(short, long, requires = "limit")]
+        start: Option<String>,
+        /// Last day of a custom budget
+        #[arg(short, long, requires = "limit")]
+        end: Option<String>,
+        /// Carry unspent (and optionally overspent) amounts into the next period (default: none)
+        #[arg(short, long, value_enum, requires = "limit")]
+        rollover: Option<Rollover>,
+        /// Maximum surplus that can be carried into a period, or `none` to remove it
+        #[arg(long, requires = "limit")]
+        rollover_cap: Option<String>,
+        /// Date a new limit takes effect (default: start of the current period)
+        #[arg(long, requires = "limit")]
+        effective_from: Option<String>,
+        /// Percentages of the limit that raise an alert, e.g. 50,75,90,100
+        #[arg(short, long, value_delimiter = ',', requires = "limit")]
+        thresholds: Option<Vec<u32>>,
+        /// Check budgets for the period containing this date instead of today
+        #[arg(long, conflicts_with = "limit")]
+        as_of: Option<String>,
     },
 
+    /// Full-text search over descriptions (with the payee and `#tags` in
+    /// them), categories and notes
     Search {
+        /// Words to find (prefix matches) or an FTS5 query
         #[arg(short, long)]
         keyword: Option<String>,
     },
-	Sql { #[arg(short, long)] query: String, },
+    Export {
+        #[arg(short, long)]
+        keyword: Option<String>,
+        /// csv, json, ledger (hledger) or beancount
+        #[arg(short, long, default_value = "csv")]
+        r#type: String,
+        /// File to write to; prints to stdout when omitted
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// List budget alerts and acknowledge them
+    Alerts {
+        /// Include acknowledged alerts
+        #[arg(long)]
+        all: bool,
+        /// Acknowledge the given alert ids, or all alerts when none are given
+        #[arg(long, value_delimiter = ',', num_args = 0..)]
+        ack: Option<Vec<i64>>,
+    },
+	Sql {
+		#[arg(short, long, required_unless_present_any = ["run", "list"])]
+		query: Option<String>,
+		/// Allow statements that modify the database
+		#[arg(long)]
+		write: bool,
+		/// Save the query under this name (and run it)
+		#[arg(long, requires = "query")]
+		save: Option<String>,
+		/// Run a previously saved query
+		#[arg(long, conflicts_with_all = ["query", "list"])]
+		run: Option<String>,
+		/// List saved queries
+		#[arg(long, conflicts_with = "query")]
+		list: bool,
+	},
 	Tui,
 
+    /// Project daily account balances from recurring transactions and budgets
+    Forecast {
+        /// Number of days after today to project
+        #[arg(short, long, default_value_t = 90)]
+        days: u32,
+    },
+
+    /// Scheduled transactions, such as rent or salary, posted when they fall due
+    Recurring {
+        #[command(subcommand)]
+        action: RecurringAction,
+    },
+
+    /// List recurring charges detected in the transactions
+    Subscriptions,
+
+    /// Show net worth over time and balances by account
+    #[command(args_conflicts_with_subcommands = true)]
+    Networth {
+        #[command(subcommand)]
+        action: Option<NetworthAction>,
+        /// Number of month-ends up to and including the current month
+        #[arg(short, long, default_value_t = 12)]
+        months: u32,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RecurringAction {
+    /// Schedule a recurring transaction
+    Add {
+        #[arg(short = 'a', long, allow_hyphen_values = true)]
+        amount: f64,
+        #[arg(short = 'c', long)]
+        category: String,
+        #[arg(short = 'd', long)]
+        description: String,
+        #[arg(long)]
+        account: Option<String>,
+        /// monthly[:DAY|last], last-business-day, quarterly[:DAY], yearly,
+        /// weekly, biweekly or every:N(d|w|m)
+        #[arg(short, long)]
+        rule: String,
+        /// First due date or the day counting starts from (default: today)
+        #[arg(short, long)]
+        start: Option<String>,
+        /// Last day the rule can fall due
+        #[arg(short, long)]
+        end: Option<String>,
+        /// Only post with `recurring post`, not automatically on startup
+        #[arg(long)]
+        no_auto: bool,
+    },
+    /// List recurring transactions with their next due date
+    List,
+    /// Delete a recurring transaction; what it already posted is kept
+    Remove { id: i64 },
+    /// Post every instance that has fallen due, including those not posted automatically
+    Post {
+        /// Show what would be posted without posting it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum NetworthAction {
+    /// Create or update an account
+    Account {
+        name: String,
+        #[arg(short, long, value_enum, default_value_t = AccountKind::Asset)]
+        kind: AccountKind,
+        /// Balance before the first transaction (the amount owed for a liability)
+        #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+        opening: f64,
+        /// Enter balances by hand with `snapshot` instead of computing them from transactions
+        #[arg(long)]
+        manual: bool,
+        /// Transactions without an account belong to this one
+        #[arg(long, conflicts_with = "manual")]
+        default: bool,
+    },
+    /// Record the balance of a manual account on a date
+    Snapshot {
+        account: String,
+        #[arg(allow_hyphen_values = true)]
+        balance: f64,
+        /// MM/DD/YYYY or YYYY-MM-DD (default: today)
+        #[arg(short, long)]
+        date: Option<String>,
+    },
+    /// List accounts with their balance
+    Accounts {
+        /// Balances at the end of this date instead of today
+        #[arg(long)]
+        as_of: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BudgetAction {
+    /// Show a budget period by period, with opening and carried balances
+    Ledger {
+        category: String,
+    },
+    /// List the limit changes of a budget
+    History {
+        category: String,
+    },
+    /// Show envelopes and the money left to be budgeted for a month
+    Envelopes {
+        /// YYYY-MM (default: current month)
+        #[arg(short, long)]
+        month: Option<String>,
+    },
+    /// Assign money from "to be budgeted" to an envelope
+    Assign {
+        category: String,
+        #[arg(allow_hyphen_values = true)]
+        amount: f64,
+        #[arg(short, long)]
+        month: Option<String>,
+    },
+    /// Move money between envelopes
+    Move {
+        from: String,
+        to: String,
+        amount: f64,
+        #[arg(short, long)]
+        month: Option<String>,
+    },
+    /// Suggest limits from past spending, next to the current ones
+    Suggest {
+        /// Number of full months of history to use
+        #[arg(short, long, default_value_t = 6)]
+        months: u32,
+        #[arg(long, value_enum, default_value_t = SuggestMethod::Median)]
+        method: SuggestMethod,
+        /// Percentile used by `--method percentile`
+        #[arg(long, default_value_t = 90, value_parser = clap::value_parser!(u32).range(1..=100))]
+        percentile: u32,
+        /// Set the suggested limits
+        #[arg(long)]
+        apply: bool,
+        /// Save the suggested limits as a template instead of applying them
+        #[arg(long, conflicts_with = "apply")]
+        save_template: Option<String>,
+    },
+    /// Save the current budgets as a named template
+    SaveTemplate {
+        name: String,
+    },
+    /// Restore the budgets saved in a template
+    ApplyTemplate {
+        name: String,
+        /// Remove budgets that aren't in the template
+        #[arg(long)]
+        replace: bool,
+    },
+    /// List saved budget templates
+    Templates,
+}
+
+#[derive(Subcommand)]
+pub enum ReportAction {
+    /// List charges and category months far above their usual amount
+    Anomalies {
+        /// Check the transactions of the last N days
+        #[arg(short, long, default_value_t = 30)]
+        days: u32,
+        /// Robust z-score from which an amount counts as unusual
+        #[arg(short, long, default_value_t = 3.5)]
+        threshold: f64,
+    },
+    /// Line up categories across periods with absolute and percentage changes
+    Compare {
+        /// Period to compare: YYYY-MM, YYYYQn or YYYY (default: current month)
+        #[arg(short, long)]
+        period: Option<String>,
+        /// Periods to compare with (default: the previous period and the same period a year earlier)
+        #[arg(short, long, value_delimiter = ',')]
+        against: Vec<String>,
+        /// Number of largest movers to list
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+    },
+    /// Cross-tabulate transactions by any two dimensions
+    Pivot {
+        /// What each row groups by
+        #[arg(short, long, value_enum, default_value_t = Dimension::Category)]
+        rows: Dimension,
+        /// What each column groups by (default: a single total column)
+        #[arg(short, long, value_enum)]
+        cols: Option<Dimension>,
+        /// How amounts in a cell are combined
+        #[arg(short, long, value_enum, default_value_t = Aggregate::Sum)]
+        value: Aggregate,
+        /// Only include matching transactions, e.g. category=Food,Groceries,
+        /// payee~uber, account!=Card or amount<0; repeat to combine
+        #[arg(short, long, allow_hyphen_values = true)]
+        filter: Vec<String>,
+        #[command(flatten)]
+        range: RangeArgs,
+    },
+    /// Show each category month by month, with totals and a sparkline
+    Trend {
+        /// Number of months up to and including the current one
+        #[arg(short, long, default_value_t = 12)]
+        months: u32,
+    },
+}
+
+/// The date range a report covers (default: the current month).
+#[derive(Args)]
+pub struct RangeArgs {
+    /// Month number, e.g. 03
+    #[arg(long, conflicts_with_all = ["from", "to", "last", "ytd", "quarter"])]
+    pub month: Option<String>,
+    /// Year; on its own, the whole year
+    #[arg(long, conflicts_with_all = ["from", "to", "last", "ytd", "quarter"])]
+    pub year: Option<String>,
+    /// First day (default: the first transaction)
+    #[arg(long, conflicts_with_all = ["last", "ytd", "quarter"])]
+    pub from: Option<String>,
+    /// Last day (default: today)
+    #[arg(long, conflicts_with_all = ["last", "ytd", "quarter"])]
+    pub to: Option<String>,
+    /// The last N days, weeks, months or years up to today, e.g. 3m
+    #[arg(long, conflicts_with_all = ["ytd", "quarter"])]
+    pub last: Option<String>,
+    /// From January 1st to today
+    #[arg(long, conflicts_with = "quarter")]
+    pub ytd: bool,
+    /// A calendar quarter, e.g. 2026Q1
+    #[arg(long)]
+    pub quarter: Option<String>,
+}
+
+impl RangeArgs {
+    pub fn spec(self) -> RangeSpec {
+        RangeSpec {
+            month: self.month,
+            year: self.year,
+            from: self.from,
+            to: self.to,
+            last: self.last,
+            ytd: self.ytd,
+            quarter: self.quarter,
+        }
+    }
 }
@@ -1,11 +1,27 @@
 use chrono::Local;
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, OpenFlags, Result};
+use rusqlite::types::ValueRef;
 use crate::models::transaction::Transaction;
+use crate::output::{Table, Value};
+
+
+const DB_PATH: &str = "finance.db";
+
+/// SQL expression that turns the `date` column into `YYYY-MM-DD`, so it can be
+/// compared and range-filtered. Imported rows are stored as `MM/DD/YYYY`.
+pub const ISO_DATE: &str = "(CASE WHEN date LIKE '__/__/____'
+	THEN substr(date, 7, 4) || '-' || substr(date, 1, 2) || '-' || substr(date, 4, 2)
+	ELSE date END)";
 
 
 pub fn init_db() -> Connection {
-    let conn = Connection::open("finance.db").expect("Failed to open finance.db");
+    let conn = Connection::open(DB_PATH).expect("Failed to open finance.db");
+    create_schema(&conn);
+    conn
+}
 
+/// Creates the tables this version needs and migrates older databases.
+fn create_schema(conn: &Connection) {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS transactions (
 			id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -16,6 +32,8 @@ pub fn init_db() -> Connection {
         )",
         [],
     ).expect("Failed to create transactions table");
+	add_column_if_missing(conn, "transactions", "account", "TEXT");
+	add_column_if_missing(conn, "transactions", "notes", "TEXT");
 	
 	conn.execute( "CREATE TABLE IF NOT EXISTS budgets ( 
 					category TEXT PRIMARY KEY, 
@@ -24,107 +42,419 @@ pub fn init_db() -> Connection {
 					[], 
 	).expect("Failed to create budgets table");
 
+	add_column_if_missing(conn, "budgets", "period", "TEXT NOT NULL DEFAULT 'monthly'");
+	add_column_if_missing(conn, "budgets", "start_date", "TEXT");
+	add_column_if_missing(conn, "budgets", "end_date", "TEXT");
+	add_column_if_missing(conn, "budgets", "rollover", "TEXT NOT NULL DEFAULT 'none'");
+	add_column_if_missing(conn, "budgets", "rollover_cap", "REAL");
+	add_column_if_missing(conn, "budgets", "thresholds", "TEXT NOT NULL DEFAULT '80,100'");
+	add_column_if_missing(conn, "budgets", "kind", "TEXT NOT NULL DEFAULT 'expense'");
+
+	// Every limit change is kept so past periods use the limit in force then.
+	// Budgets that predate the history get a row without a start date.
+	conn.execute_batch( "CREATE TABLE IF NOT EXISTS budget_history (
+					id INTEGER PRIMARY KEY AUTOINCREMENT,
+					category TEXT NOT NULL,
+					limit_amount REAL NOT NULL,
+					effective_from TEXT,
+					changed_at TEXT DEFAULT (datetime('now', 'localtime'))
+					);
+					INSERT INTO budget_history (category, limit_amount, effective_from, changed_at)
+					SELECT category, limit_amount, NULL, NULL FROM budgets
+					WHERE category NOT IN (SELECT category FROM budget_history);",
+	).expect("Failed to create budget_history table");
+
+	conn.execute( "CREATE TABLE IF NOT EXISTS budget_alerts (
+					id INTEGER PRIMARY KEY AUTOINCREMENT,
+					category TEXT NOT NULL,
+					period_start TEXT NOT NULL,
+					period_end TEXT NOT NULL,
+					threshold INTEGER NOT NULL,
+					spent REAL NOT NULL,
+					limit_amount REAL NOT NULL,
+					fired_at TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+					acknowledged INTEGER NOT NULL DEFAULT 0,
+					UNIQUE (category, period_start, threshold)
+					)",
+					[],
+	).expect("Failed to create budget_alerts table");
+
+	conn.execute( "CREATE TABLE IF NOT EXISTS envelope_allocations (
+					id INTEGER PRIMARY KEY AUTOINCREMENT,
+					month TEXT NOT NULL,
+					category TEXT NOT NULL,
+					amount REAL NOT NULL
+					)",
+					[],
+	).expect("Failed to create envelope_allocations table");
+
+	conn.execute( "CREATE TABLE IF NOT EXISTS budget_templates (
+					name TEXT NOT NULL,
+					category TEXT NOT NULL,
+					limit_amount REAL NOT NULL,
+					period TEXT NOT NULL,
+					start_date TEXT,
+					end_date TEXT,
+					rollover TEXT NOT NULL,
+					rollover_cap REAL,
+					thresholds TEXT NOT NULL,
+					PRIMARY KEY (name, category)
+					)",
+					[],
+	).expect("Failed to create budget_templates table");
+	add_column_if_missing(conn, "budget_templates", "kind", "TEXT NOT NULL DEFAULT 'expense'");
+
+	conn.execute( "CREATE TABLE IF NOT EXISTS accounts (
+					name TEXT PRIMARY KEY,
+					kind TEXT NOT NULL DEFAULT 'asset',
+					opening_balance REAL NOT NULL DEFAULT 0,
+					manual INTEGER NOT NULL DEFAULT 0,
+					is_default INTEGER NOT NULL DEFAULT 0
+					)",
+					[],
+	).expect("Failed to create accounts table");
+
+	// Balances entered by hand for accounts that aren't imported.
+	conn.execute( "CREATE TABLE IF NOT EXISTS balance_snapshots (
+					id INTEGER PRIMARY KEY AUTOINCREMENT,
+					account TEXT NOT NULL,
+					date TEXT NOT NULL,
+					balance REAL NOT NULL,
+					UNIQUE (account, date)
+					)",
+					[],
+	).expect("Failed to create balance_snapshots table");
+
+	conn.execute( "CREATE TABLE IF NOT EXISTS recurring_rules (
+					id INTEGER PRIMARY KEY AUTOINCREMENT,
+					amount REAL NOT NULL,
+					category TEXT NOT NULL,
+					description TEXT NOT NULL,
+					account TEXT,
+					rule TEXT NOT NULL,
+					start_date TEXT NOT NULL,
+					end_date TEXT,
+					auto_post INTEGER NOT NULL DEFAULT 1
+					)",
+					[],
+	).expect("Failed to create recurring_rules table");
+
+	// Every due date a rule has been handled for, with the transaction that
+	// was posted for it or the imported one that matched it.
+	conn.execute( "CREATE TABLE IF NOT EXISTS recurring_posts (
+					rule_id INTEGER NOT NULL,
+					date TEXT NOT NULL,
+					transaction_id INTEGER NOT NULL,
+					matched INTEGER NOT NULL DEFAULT 0,
+					PRIMARY KEY (rule_id, date)
+					)",
+					[],
+	).expect("Failed to create recurring_posts table");
+
+	conn.execute( "CREATE TABLE IF NOT EXISTS saved_queries (
+					name TEXT PRIMARY KEY,
+					query TEXT NOT NULL
+					)",
+					[],
+	).expect("Failed to create saved_queries table");
+
+    init_search_index(conn);
+}
+
+/// An empty in-memory database with the full schema, for tests.
+#[cfg(test)]
+pub fn test_db() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    create_schema(&conn);
     conn
 }
 
 
-pub fn add_transaction(conn: &Connection, amount: f64, category: &str, description: &str, date: Option<String>) {
+// `CREATE TABLE IF NOT EXISTS` leaves older databases untouched, so columns
+// added later are appended here.
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) {
+    let exists: bool = conn
+        .query_row(
+            &format!("SELECT COUNT(*) > 0 FROM pragma_table_info('{}') WHERE name = ?1", table),
+            [column],
+            |row| row.get(0),
+        )
+        .expect("Failed to inspect table");
+
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])
+            .unwrap_or_else(|e| panic!("Failed to add {}.{}: {}", table, column, e));
+    }
+}
+
+
+// Full-text index over the searchable transaction columns. It is an external
+// content table, so the text lives only in `transactions`; the triggers keep
+// the index in step with every insert, update and delete. The payee and
+// `#tags` are part of the description, so they are indexed with it.
+fn init_search_index(conn: &Connection) {
+    // Indexes from before notes were searchable are rebuilt with them.
+    let has_notes: bool = conn
+        .query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('transactions_fts') WHERE name = 'notes'",
+            [],
+            |row| row.get(0),
+        )
+        .expect("Failed to inspect search index");
+    if !has_notes {
+        conn.execute_batch(
+            "DROP TRIGGER IF EXISTS transactions_fts_ai;
+			DROP TRIGGER IF EXISTS transactions_fts_ad;
+			DROP TRIGGER IF EXISTS transactions_fts_au;
+			DROP TABLE IF EXISTS transactions_fts;",
+        ).expect("Failed to drop old search index");
+    }
+
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS transactions_fts USING fts5(
+			description,
+			category,
+			notes,
+			content = 'transactions',
+			content_rowid = 'id',
+			tokenize = 'unicode61 remove_diacritics 2'
+		);
+
+		CREATE TRIGGER IF NOT EXISTS transactions_fts_ai AFTER INSERT ON transactions BEGIN
+			INSERT INTO transactions_fts (rowid, description, category, notes)
+			VALUES (new.id, new.description, new.category, new.notes);
+		END;
+
+		CREATE TRIGGER IF NOT EXISTS transactions_fts_ad AFTER DELETE ON transactions BEGIN
+			INSERT INTO transactions_fts (transactions_fts, rowid, description, category, notes)
+			VALUES ('delete', old.id, old.description, old.category, old.notes);
+		END;
+
+		CREATE TRIGGER IF NOT EXISTS transactions_fts_au AFTER UPDATE ON transactions BEGIN
+			INSERT INTO transactions_fts (transactions_fts, rowid, description, category, notes)
+			VALUES ('delete', old.id, old.description, old.category, old.notes);
+			INSERT INTO transactions_fts (rowid, description, category, notes)
+			VALUES (new.id, new.description, new.category, new.notes);
+		END;",
+    ).expect("Failed to create search index");
+
+    // Databases created before the index existed need a one-off rebuild.
+    let indexed: i64 = conn
+        .query_row("SELECT COUNT(*) FROM transactions_fts_docsize", [], |row| row.get(0))
+        .expect("Failed to inspect search index");
+    let stored: i64 = conn
+        .query_row("SELECT COUNT(*) FROM transactions", [], |row| row.get(0))
+        .expect("Failed to count transactions");
+
+    if indexed != stored {
+        conn.execute("INSERT INTO transactions_fts (transactions_fts) VALUES ('rebuild')", [])
+            .expect("Failed to rebuild search index");
+    }
+}
+
+
+pub fn add_transaction(conn: &Connection, amount: f64, category: &str, description: &str, date: Option<String>, account: Option<&str>) -> i64 {
     let date_str = date.unwrap_or_else(|| Local::now().format("%Y-%m-%d").to_string());
     conn.execute(
-        "INSERT INTO transactions (amount, category, description, date) VALUES (?1, ?2, ?3, ?4)",
-        params![amount, category, description, date_str],
+        "INSERT INTO transactions (amount, category, description, date, account) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![amount, category, description, date_str, account],
     ).expect("Failed to insert transaction");
 
-    println!("Saved: {} in '{}' on {}", amount, category, date_str);
+    conn.last_insert_rowid()
 }
 
 
+/// Attaches free-form notes to a transaction; they are searchable.
+pub fn set_notes(conn: &Connection, id: i64, notes: &str) {
+    conn.execute("UPDATE transactions SET notes = ?1 WHERE id = ?2", params![notes, id])
+        .expect("Failed to save notes");
+}
 
 
+pub fn transactions_table(name: &str, transactions: &[Transaction]) -> Table {
+    let mut table = Table::new(name, &["id", "amount", "category", "description", "date", "account", "notes"]);
+    for t in transactions {
+        table.push(vec![
+            t.id.into(),
+            t.amount.into(),
+            t.category.clone().into(),
+            t.description.clone().into(),
+            t.date.clone().into(),
+            t.account.clone().into(),
+            t.notes.clone().into(),
+        ]);
+    }
+    table
+}
 
-pub fn search_transactions(conn: &Connection, keyword: Option<String>) -> Result<()> {
-    let (sql, params) = if let Some(k) = keyword {
-        (
-            "SELECT id, amount, category, description, date FROM transactions WHERE category LIKE ?1",
-            params![format!("%{}%", k)],
-        )
-    } else {
-        (
-            "SELECT id, amount, category, description, date FROM transactions",
-            params![],
-        )
+
+pub fn search_transactions(conn: &Connection, keyword: Option<String>) -> Result<Table> {
+    let transactions = find_transactions(conn, keyword)?;
+    Ok(transactions_table("transactions", &transactions))
+}
+
+
+/// Loads the transactions matching `keyword` (all of them when `None`),
+/// ranked by relevance when a keyword is given.
+pub fn find_transactions(conn: &Connection, keyword: Option<String>) -> Result<Vec<Transaction>> {
+    let Some(keyword) = keyword else {
+        let mut stmt = conn.prepare("SELECT id, amount, category, description, date, account, notes FROM transactions")?;
+        let rows = stmt.query_map([], transaction_from_row)?;
+        return rows.collect();
     };
 
-    let mut stmt = conn.prepare(sql)?;
-    let rows = stmt.query_map(params, |row| {
-        Ok((
-            row.get::<_, i32>(0)?,
-            row.get::<_, f64>(1)?,
-            row.get::<_, String>(2)?,
-			row.get::<_, String>(3)?,
-            row.get::<_, String>(4)?,
-        ))
-    })?;
+    // Text that only looks like FTS5 syntax, e.g. `3:30` or a stray quote,
+    // is searched for as plain words instead.
+    match_transactions(conn, &fts_query(&keyword))
+        .or_else(|_| match_transactions(conn, &prefix_terms(&keyword)))
+}
+
+fn match_transactions(conn: &Connection, query: &str) -> Result<Vec<Transaction>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.amount, t.category, t.description, t.date, t.account, t.notes
+         FROM transactions_fts
+         JOIN transactions t ON t.id = transactions_fts.rowid
+         WHERE transactions_fts MATCH ?1
+         ORDER BY bm25(transactions_fts), t.id",
+    )?;
+    let rows = stmt.query_map([query], transaction_from_row)?;
+
+    rows.collect()
+}
+
+
+/// Turns a search keyword into an FTS5 query.
+///
+/// Plain words become prefix terms, so `ub` finds "uber". Anything already
+/// written in FTS5 syntax (quoted phrases, `*`, `AND`/`OR`/`NOT`, column
+/// filters) is passed through untouched.
+fn fts_query(keyword: &str) -> String {
+    let is_fts_syntax = keyword.contains(['"', '*', ':', '(', ')'])
+        || keyword
+            .split_whitespace()
+            .any(|w| matches!(w, "AND" | "OR" | "NOT" | "NEAR"));
 
-    for row in rows {
-        let (id, amount, category, description, date) = row?;
-        println!("{} | {} | {} | {} | {}", id, amount, category, description, date);
+    if is_fts_syntax {
+        return keyword.to_string();
     }
 
-    Ok(())
+    prefix_terms(keyword)
+}
+
+/// Every word of `keyword` as a quoted prefix term, so nothing in it is
+/// read as FTS5 syntax.
+fn prefix_terms(keyword: &str) -> String {
+    keyword
+        .split_whitespace()
+        .map(|w| format!("\"{}\"*", w.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 
 
-pub fn import_transactions(conn: &Connection, file_path: &str, file_type: &str) {
-    if file_type != "csv" {
-        println!("Only CSV import is supported for now.");
-        return;
+pub fn import_transactions(conn: &Connection, file_path: &str, file_type: &str, account: Option<&str>) -> Result<usize, String> {
+    let transactions = match file_type {
+        "csv" => crate::parser::parse_csv(file_path),
+        "ledger" | "hledger" => crate::parser::parse_ledger(file_path),
+        "beancount" => crate::parser::parse_beancount(file_path),
+        other => return Err(format!("Unsupported import type '{}'. Use csv, ledger or beancount.", other)),
+    };
+
+    // `--account` wins over accounts named in the file, which are created
+    // as needed.
+    for tx in &transactions {
+        let tx_account = account.or(tx.account.as_deref());
+        if account.is_none()
+            && let Some(name) = tx_account
+        {
+            ensure_account(conn, name, tx.account_kind);
+        }
+        add_transaction(conn, tx.amount, &tx.category, &tx.description, Some(tx.date.clone()), tx_account);
     }
 
-    let transactions = crate::parser::parse_csv(file_path);
-    for tx in transactions {
-        add_transaction(conn, tx.amount, &tx.category, &tx.description, Some(tx.date));
+    Ok(transactions.len())
+}
+
+/// Creates an account computed from transactions unless one with this name exists.
+pub fn ensure_account(conn: &Connection, name: &str, kind: AccountKind) {
+    if load_account(conn, name).is_err() {
+        crate::networth::set_account(conn, &Account {
+            name: name.to_string(),
+            kind,
+            opening_balance: 0.0,
+            manual: false,
+            is_default: false,
+        });
     }
+}
 
-    println!("Import completed.");
+/// Opens a second connection that SQLite itself refuses to write through,
+/// used by the `sql` subcommand unless `--write` is given.
+pub fn open_read_only() -> Connection {
+    Connection::open_with_flags(DB_PATH, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .expect("Failed to open finance.db")
 }
 
-pub fn run_sql(conn: &Connection, query: &str) {
-    let mut stmt = conn.prepare(query).expect("Invalid SQL");
-    let column_count = stmt.column_count();
+pub fn save_query(conn: &Connection, name: &str, query: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO saved_queries (name, query) VALUES (?1, ?2)
+         ON CONFLICT(name) DO UPDATE SET query = excluded.query",
+        (name, query),
+    )?;
+    Ok(())
+}
+
+pub fn load_query(conn: &Connection, name: &str) -> Result<String> {
+    conn.query_row("SELECT query FROM saved_queries WHERE name = ?1", [name], |row| row.get(0))
+}
 
-    let rows = stmt
-        .query_map([], |row| {
-            let mut values = Vec::new();
-            for i in 0..column_count {
-                let v: Result<String, _> = row.get(i);
-                values.push(v.unwrap_or("NULL".into()));
-            }
-            Ok(values)
-        })
-        .expect("Query failed");
+pub fn saved_queries(conn: &Connection) -> Result<Table> {
+    let mut table = run_sql(conn, "SELECT name, query FROM saved_queries ORDER BY name")?;
+    table.name = "saved_queries".to_string();
+    Ok(table)
+}
+
+pub fn run_sql(conn: &Connection, query: &str) -> Result<Table> {
+    let mut stmt = conn.prepare(query)?;
+
+    // Statements like UPDATE return no columns; report what they changed.
+    if stmt.column_count() == 0 {
+        let changed = stmt.execute([])?;
+        let mut table = Table::new("result", &["rows_affected"]);
+        table.push(vec![changed.into()]);
+        return Ok(table);
+    }
 
-    for row in rows {
-        println!("{:?}", row.unwrap());
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let column_refs: Vec<&str> = columns.iter().map(String::as_str).collect();
+    let mut table = Table::new("rows", &column_refs);
+
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let mut values = Vec::with_capacity(columns.len());
+        for i in 0..columns.len() {
+            values.push(match row.get_ref(i)? {
+                ValueRef::Null => Value::Null,
+                ValueRef::Integer(v) => Value::Int(v),
+                ValueRef::Real(v) => Value::Real(v),
+                ValueRef::Text(v) => Value::Text(String::from_utf8_lossy(v).into_owned()),
+                ValueRef::Blob(v) => Value::Text(format!("<{} bytes>", v.len())),
+            });
+        }
+        table.push(values);
     }
+
+    Ok(table)
 }
 
 
 pub fn load_transactions(conn: &Connection) -> Result<Vec<Transaction>, rusqlite::Error> {
     let mut stmt = conn.prepare(
-        "SELECT id, amount, category, description, date FROM transactions ORDER BY id ASC"
+        "SELECT id, amount, category, description, date, account, notes FROM transactions ORDER BY id ASC"
     )?;
 
-    let rows = stmt.query_map([], |row| {
-        Ok(Transaction {
-            id: row.get(0)?,
-            amount: row.get(1)?,
-            category: row.get(2)?,
-            description: row.get(3)?,
-            date: row.get(4)?,
-        })
-    })?;
+    let rows = stmt.query_map([], transaction_from_row)?;
 
     let mut transactions = Vec::new();
     for t in rows {
@@ -134,19 +464,57 @@ pub fn load_transactions(conn: &Connection) -> Result<Vec<Transaction>, rusqlite
     Ok(transactions)
 }
 
-use crate::models::budget::Budget;
+pub fn load_transaction(conn: &Connection, id: i64) -> Result<Transaction, rusqlite::Error> {
+    conn.query_row(
+        "SELECT id, amount, category, description, date, account, notes FROM transactions WHERE id = ?1",
+        [id],
+        transaction_from_row,
+    )
+}
+
+use crate::models::account::{Account, AccountKind};
+use crate::models::budget::{parse_thresholds, Budget, BudgetKind, Period, Rollover};
+use crate::models::recurring::{RecurringRule, Schedule};
+
+fn transaction_from_row(row: &rusqlite::Row) -> Result<Transaction> {
+    Ok(Transaction {
+        id: row.get(0)?,
+        amount: row.get(1)?,
+        category: row.get(2)?,
+        description: row.get(3)?,
+        date: row.get(4)?,
+        account: row.get(5)?,
+        notes: row.get(6)?,
+    })
+}
+
+fn budget_from_row(row: &rusqlite::Row) -> Result<Budget> {
+    let period: String = row.get(2)?;
+    let start: Option<String> = row.get(3)?;
+    let end: Option<String> = row.get(4)?;
+    let rollover: String = row.get(5)?;
+    let thresholds: String = row.get(7)?;
+    let kind: String = row.get(8)?;
+
+    Ok(Budget {
+        category: row.get(0)?,
+        limit_amount: row.get(1)?,
+        kind: BudgetKind::parse(&kind).unwrap_or(BudgetKind::Expense),
+        period: Period::parse(&period).unwrap_or(Period::Monthly),
+        start_date: start.as_deref().and_then(crate::parser::parse_date),
+        end_date: end.as_deref().and_then(crate::parser::parse_date),
+        rollover: Rollover::parse(&rollover).unwrap_or(Rollover::None),
+        rollover_cap: row.get(6)?,
+        thresholds: parse_thresholds(&thresholds),
+    })
+}
 
 pub fn load_budgets(conn: &Connection) -> Result<Vec<Budget>, rusqlite::Error> {
     let mut stmt = conn.prepare(
-        "SELECT category, limit_amount FROM budgets ORDER BY category ASC"
+        "SELECT category, limit_amount, period, start_date, end_date, rollover, rollover_cap, thresholds, kind FROM budgets ORDER BY category ASC"
     )?;
 
-    let rows = stmt.query_map([], |row| {
-        Ok(Budget {
-            category: row.get(0)?,
-            limit_amount: row.get(1)?,
-        })
-    })?;
+    let rows = stmt.query_map([], budget_from_row)?;
 
     let mut budgets = Vec::new();
     for b in rows {
@@ -156,6 +524,125 @@ pub fn load_budgets(conn: &Connection) -> Result<Vec<Budget>, rusqlite::Error> {
     Ok(budgets)
 }
 
+pub fn load_template(conn: &Connection, name: &str) -> Result<Vec<Budget>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT category, limit_amount, period, start_date, end_date, rollover, rollover_cap, thresholds, kind FROM budget_templates WHERE name = ?1 ORDER BY category ASC"
+    )?;
 
+    let rows = stmt.query_map([name], budget_from_row)?;
+    rows.collect()
+}
 
+fn account_from_row(row: &rusqlite::Row) -> Result<Account> {
+    let kind: String = row.get(1)?;
+    Ok(Account {
+        name: row.get(0)?,
+        kind: AccountKind::parse(&kind).unwrap_or(AccountKind::Asset),
+        opening_balance: row.get(2)?,
+        manual: row.get(3)?,
+        is_default: row.get(4)?,
+    })
+}
 
+pub fn load_accounts(conn: &Connection) -> Result<Vec<Account>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT name, kind, opening_balance, manual, is_default FROM accounts ORDER BY kind, name"
+    )?;
+    let rows = stmt.query_map([], account_from_row)?;
+    rows.collect()
+}
+
+pub fn load_account(conn: &Connection, name: &str) -> Result<Account, rusqlite::Error> {
+    conn.query_row(
+        "SELECT name, kind, opening_balance, manual, is_default FROM accounts WHERE name = ?1",
+        [name],
+        account_from_row,
+    )
+}
+
+fn rule_from_row(row: &rusqlite::Row) -> Result<RecurringRule> {
+    let rule: String = row.get(5)?;
+    let start: String = row.get(6)?;
+    let end: Option<String> = row.get(7)?;
+    let start_date = crate::parser::parse_date(&start).unwrap_or_default();
+    Ok(RecurringRule {
+        id: row.get(0)?,
+        amount: row.get(1)?,
+        category: row.get(2)?,
+        description: row.get(3)?,
+        account: row.get(4)?,
+        schedule: Schedule::parse(&rule, start_date).expect("Invalid recurring rule"),
+        rule,
+        start_date,
+        end_date: end.as_deref().and_then(crate::parser::parse_date),
+        auto_post: row.get(8)?,
+    })
+}
+
+pub fn load_rules(conn: &Connection) -> Result<Vec<RecurringRule>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT id, amount, category, description, account, rule, start_date, end_date, auto_post FROM recurring_rules ORDER BY id"
+    )?;
+    let rows = stmt.query_map([], rule_from_row)?;
+    rows.collect()
+}
+
+pub fn load_budget(conn: &Connection, category: &str) -> Result<Budget, rusqlite::Error> {
+    conn.query_row(
+        "SELECT category, limit_amount, period, start_date, end_date, rollover, rollover_cap, thresholds, kind FROM budgets WHERE category = ?1",
+        [category],
+        budget_from_row,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(conn: &Connection, keyword: &str) -> Vec<String> {
+        find_transactions(conn, Some(keyword.to_string()))
+            .unwrap()
+            .into_iter()
+            .filter_map(|t| t.description)
+            .collect()
+    }
+
+    #[test]
+    fn finds_words_by_prefix() {
+        let conn = test_db();
+        add_transaction(&conn, -30.0, "Transport", "Uber ride #work", Some("2024-03-01".to_string()), None);
+        add_transaction(&conn, -12.0, "Food", "Lunch", Some("2024-03-02".to_string()), None);
+        assert_eq!(search(&conn, "ub"), vec!["Uber ride #work"]);
+        assert_eq!(search(&conn, "work"), vec!["Uber ride #work"]);
+        assert_eq!(search(&conn, "food"), vec!["Lunch"]);
+    }
+
+    #[test]
+    fn text_that_looks_like_fts_syntax_is_searched_as_words() {
+        let conn = test_db();
+        add_transaction(&conn, -50.0, "Health", "Dentist 3:30 appointment", Some("2024-03-01".to_string()), None);
+        assert_eq!(search(&conn, "3:30"), vec!["Dentist 3:30 appointment"]);
+        assert_eq!(search(&conn, "\"dentist"), vec!["Dentist 3:30 appointment"]);
+        assert!(search(&conn, "(unbalanced").is_empty());
+    }
+
+    #[test]
+    fn finds_notes() {
+        let conn = test_db();
+        let id = add_transaction(&conn, -20.0, "Health", "Pharmacy", Some("2024-03-01".to_string()), None);
+        set_notes(&conn, id, "ibuprofen for Ana");
+        assert_eq!(search(&conn, "ibupro"), vec!["Pharmacy"]);
+        set_notes(&conn, id, "vitamins");
+        assert!(search(&conn, "ibupro").is_empty());
+        assert_eq!(search(&conn, "notes:vitamins"), vec!["Pharmacy"]);
+    }
+
+    #[test]
+    fn fts_syntax_still_works() {
+        let conn = test_db();
+        add_transaction(&conn, -30.0, "Transport", "Uber ride", Some("2024-03-01".to_string()), None);
+        add_transaction(&conn, -12.0, "Food", "Uber Eats", Some("2024-03-02".to_string()), None);
+        assert_eq!(search(&conn, "uber NOT eats"), vec!["Uber ride"]);
+        assert_eq!(search(&conn, "category:food"), vec!["Uber Eats"]);
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use crate::models::account::{Account, AccountKind};
+use crate::models::transaction::Transaction;
+use crate::output::{self, Format, Table};
+use crate::parser::parse_date;
+
+// Plain-text accounting needs a balancing account and a commodity. Amounts
+// are in lei; transactions without an account (and no default account set
+// up) balance against this one.
+const ASSET_ACCOUNT: &str = "Assets:Checking";
+const COMMODITY: &str = "RON";
+
+
+pub fn export_transactions(
+    conn: &Connection,
+    keyword: Option<String>,
+    file_type: &str,
+    output: Option<&str>,
+) -> Result<usize, String> {
+    let transactions = crate::db::find_transactions(conn, keyword).map_err(|e| e.to_string())?;
+
+    let accounts = crate::db::load_accounts(conn).map_err(|e| e.to_string())?;
+
+    let text = match file_type {
+        "csv" => to_csv(&transactions),
+        "json" => serde_json::to_string_pretty(&transactions).map_err(|e| e.to_string())? + "\n",
+        "ledger" | "hledger" => to_ledger(&transactions, &accounts)?,
+        "beancount" => to_beancount(&transactions, &accounts)?,
+        other => return Err(format!("Unsupported export type '{}'. Use csv, json, ledger or beancount.", other)),
+    };
+
+    match output {
+        Some(path) => fs::write(path, text).map_err(|e| format!("Failed to write {}: {}", path, e))?,
+        None => {
+            let _ = io::stdout().lock().write_all(text.as_bytes());
+        }
+    }
+
+    Ok(transactions.len())
+}
+
+
+// Same column layout as `import`, so an export can be imported again.
+// Fields with commas or quotes are quoted, which `import` understands.
+fn to_csv(transactions: &[Transaction]) -> String {
+    let mut table = Table::new("transactions", &["amount", "category", "description", "date", "account"]);
+    for t in transactions {
+        table.push(vec![
+            t.amount.into(),
+            t.category.clone().into(),
+            t.description.clone().into(),
+            t.date.clone().into(),
+            t.account.clone().into(),
+        ]);
+    }
+    output::render(&[table], Format::Csv)
+}
+
+
+/// `root:name` with `name` made valid in both hledger and beancount
+/// (capitalised, no spaces), or `root:<fallback>` when it's empty.
+fn journal_account(root: &str, name: &str, fallback: &str) -> String {
+    let mut name: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    if name.is_empty() {
+        name = fallback.to_string();
+    }
+    if let Some(first) = name.chars().next() {
+        name.replace_range(..first.len_utf8(), &first.to_uppercase().to_string());
+    }
+    if !name.starts_with(|c: char| c.is_alphabetic()) {
+        name.insert(0, 'X');
+    }
+
+    format!("{}:{}", root, name)
+}
+
+/// Maps a category onto an expense or income account name.
+fn category_account(t: &Transaction) -> String {
+    let root = if t.amount < 0.0 { "Expenses" } else { "Income" };
+    journal_account(root, t.category.as_deref().unwrap_or(""), "Uncategorized")
+}
+
+/// The asset or liability account the money moved through: the
+/// transaction's own account, else the default one.
+fn balancing_account(t: &Transaction, accounts: &[Account]) -> String {
+    let account = match &t.account {
+        Some(name) => accounts.iter().find(|a| &a.name == name),
+        None => accounts.iter().find(|a| a.is_default),
+    };
+    match (account, &t.account) {
+        (Some(a), _) => {
+            let root = if a.kind == AccountKind::Liability { "Liabilities" } else { "Assets" };
+            journal_account(root, &a.name, "Checking")
+        }
+        (None, Some(name)) => journal_account("Assets", name, "Checking"),
+        (None, None) => ASSET_ACCOUNT.to_string(),
+    }
+}
+
+fn journal_date(t: &Transaction) -> Result<NaiveDate, String> {
+    parse_date(&t.date).ok_or_else(|| format!("Transaction {} has an invalid date '{}'", t.id, t.date))
+}
+
+fn to_ledger(transactions: &[Transaction], accounts: &[Account]) -> Result<String, String> {
+    let mut out = String::new();
+
+    for t in transactions {
+        let date = journal_date(t)?;
+        let description = t.description.as_deref().unwrap_or("").replace(';', ",");
+
+        out.push_str(&format!("{} {}  ; id:{}\n", date.format("%Y-%m-%d"), description, t.id));
+        out.push_str(&format!("    {:<40}  {:>12.2} {}\n", category_account(t), -t.amount, COMMODITY));
+        out.push_str(&format!("    {:<40}  {:>12.2} {}\n\n", balancing_account(t, accounts), t.amount, COMMODITY));
+    }
+
+    Ok(out)
+}
+
+fn to_beancount(transactions: &[Transaction], accounts: &[Account]) -> Result<String, String> {
+    // Beancount rejects postings to accounts that were never opened, so open
+    // each one on the date it is first used.
+    let mut opened: BTreeMap<String, NaiveDate> = BTreeMap::new();
+    let mut body = String::new();
+
+    for t in transactions {
+        let date = journal_date(t)?;
+        let account = category_account(t);
+        let balancing = balancing_account(t, accounts);
+        for name in [account.clone(), balancing.clone()] {
+            let first = opened.entry(name).or_insert(date);
+            *first = (*first).min(date);
+        }
+
+        let description = t.description.as_deref().unwrap_or("").replace('"', "'");
+        body.push_str(&format!("{} * \"{}\"\n", date.format("%Y-%m-%d"), description));
+        body.push_str(&format!("  id: {}\n", t.id));
+        body.push_str(&format!("  {:<40}  {:>12.2} {}\n", account, -t.amount, COMMODITY));
+        body.push_str(&format!("  {:<40}  {:>12.2} {}\n\n", balancing, t.amount, COMMODITY));
+    }
+
+    let mut out = format!("option \"operating_currency\" \"{}\"\n\n", COMMODITY);
+    for (account, date) in &opened {
+        out.push_str(&format!("{} open {}\n", date.format("%Y-%m-%d"), account));
+    }
+    out.push('\n');
+    out.push_str(&body);
+
+    Ok(out)
+}
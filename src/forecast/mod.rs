@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use chrono::{Days, Months, NaiveDate};
+use rusqlite::Connection;
+use crate::budget::{limit_on, period_window, spent_between};
+use crate::models::account::{Account, AccountKind};
+use crate::models::budget::{Budget, BudgetKind, Period};
+use crate::models::recurring::RecurringRule;
+use crate::networth::balance_on;
+use crate::output::{Table, Value};
+use crate::recurring::detect::{self, Recurring};
+
+/// One projected day, with each account's balance at the end of it.
+pub struct Day {
+    pub date: NaiveDate,
+    pub inflow: f64,
+    pub outflow: f64,
+    pub balances: Vec<f64>,
+    /// The recurring transactions expected that day
+    pub events: Vec<String>,
+}
+
+pub struct Forecast {
+    /// Accounts whose balances are projected, in the order of `Day::balances`
+    pub accounts: Vec<Account>,
+    pub opening: Vec<f64>,
+    pub days: Vec<Day>,
+    pub expected: Vec<Expected>,
+}
+
+/// Accounts that can run dry: asset accounts computed from transactions. With
+/// no accounts set up, all transactions are projected as one balance.
+fn projected_accounts(accounts: Vec<Account>) -> Vec<Account> {
+    if accounts.is_empty() {
+        return vec![Account {
+            name: "all".to_string(),
+            kind: AccountKind::Asset,
+            opening_balance: 0.0,
+            manual: false,
+            is_default: true,
+        }];
+    }
+    accounts.into_iter().filter(|a| a.kind == AccountKind::Asset && !a.manual).collect()
+}
+
+/// Daily discretionary spending for an expense budget: whatever its limit
+/// leaves after spending so far and the recurring charges still expected in
+/// the period, spread evenly over the period's remaining days.
+fn budgeted_per_day(
+    conn: &Connection,
+    budget: &Budget,
+    window: (NaiveDate, NaiveDate),
+    recurring: f64,
+    today: NaiveDate,
+) -> f64 {
+    let limit = limit_on(conn, &budget.category, window.0).unwrap_or(budget.limit_amount);
+    let (left, days) = if window.0 <= today {
+        (limit - spent_between(conn, &budget.category, window.0, today) - recurring, (window.1 - today).num_days())
+    } else {
+        (limit - recurring, (window.1 - window.0).num_days() + 1)
+    };
+    if days > 0 { left.max(0.0) / days as f64 } else { 0.0 }
+}
+
+/// A transaction expected to repeat, from a schedule or found in the history.
+pub struct Expected {
+    pub description: String,
+    pub category: Option<String>,
+    pub account: Option<String>,
+    /// `schedule` for `recurring` rules, otherwise the detected cadence
+    pub source: &'static str,
+    pub amount: f64,
+    /// Due dates after today, up to a year past the end of the forecast
+    pub dates: Vec<NaiveDate>,
+}
+
+impl Expected {
+    fn from_rule(rule: &RecurringRule, today: NaiveDate, horizon: NaiveDate) -> Self {
+        Expected {
+            description: rule.description.clone(),
+            category: Some(rule.category.clone()),
+            account: rule.account.clone(),
+            source: "schedule",
+            amount: rule.amount,
+            dates: rule.due_dates(horizon).into_iter().filter(|d| *d > today).collect(),
+        }
+    }
+
+    fn from_detected(r: &Recurring, today: NaiveDate, horizon: NaiveDate) -> Self {
+        Expected {
+            description: r.description.clone(),
+            category: r.category.clone(),
+            account: r.account.clone(),
+            source: r.cadence.as_str(),
+            amount: r.amount(),
+            dates: r.upcoming(horizon).into_iter().filter(|d| *d > today).collect(),
+        }
+    }
+
+    /// Whether a detected series is the history of this scheduled one.
+    fn same_as(&self, r: &Recurring) -> bool {
+        self.description.to_lowercase() == r.description.to_lowercase()
+            && self.category == r.category
+            && self.account == r.account
+    }
+}
+
+/// Projects each account's balance for the `days` days after `today` from
+/// its current balance, the scheduled recurring transactions and those found
+/// in its history, and the unspent part of every expense budget, charged to
+/// the default account.
+pub fn forecast(
+    conn: &Connection,
+    accounts: Vec<Account>,
+    rules: &[RecurringRule],
+    budgets: &[Budget],
+    days: u32,
+    today: NaiveDate,
+) -> Forecast {
+    let accounts = projected_accounts(accounts);
+    let end = today + Days::new(days as u64);
+    // Far enough to cover the last budget period the forecast touches.
+    let horizon = end + Months::new(12);
+    let index = |name: &Option<String>| match name {
+        Some(name) => accounts.iter().position(|a| &a.name == name),
+        None => accounts.iter().position(|a| a.is_default),
+    };
+
+    let mut expected: Vec<Expected> = rules.iter().map(|r| Expected::from_rule(r, today, horizon)).collect();
+    let scheduled = expected.len();
+    for r in detect::detect(conn) {
+        if !r.is_stale(today) && !expected[..scheduled].iter().any(|e| e.same_as(&r)) {
+            expected.push(Expected::from_detected(&r, today, horizon));
+        }
+    }
+    expected.retain(|e| index(&e.account).is_some() && !e.dates.is_empty());
+
+    let expected_spending = |category: &str, from: NaiveDate, to: NaiveDate| -> f64 {
+        expected
+            .iter()
+            .filter(|e| e.category.as_deref() == Some(category) && e.amount < 0.0)
+            .map(|e| e.dates.iter().filter(|d| (from..=to).contains(*d)).count() as f64 * -e.amount)
+            .sum()
+    };
+
+    let default = index(&None);
+    let mut rates: HashMap<(usize, NaiveDate), f64> = HashMap::new();
+    let mut balances: Vec<f64> = accounts.iter().map(|a| balance_on(conn, a, today)).collect();
+    let opening = balances.clone();
+    let mut projected = Vec::new();
+
+    for offset in 1..=days as u64 {
+        let date = today + Days::new(offset);
+        let mut day = Day { date, inflow: 0.0, outflow: 0.0, balances: Vec::new(), events: Vec::new() };
+
+        for e in expected.iter().filter(|e| e.dates.contains(&date)) {
+            balances[index(&e.account).unwrap()] += e.amount;
+            if e.amount >= 0.0 { day.inflow += e.amount } else { day.outflow -= e.amount }
+            day.events.push(format!("{} {:+.2}", e.description, e.amount));
+        }
+
+        if let Some(default) = default {
+            for (i, budget) in budgets.iter().enumerate().filter(|(_, b)| b.kind == BudgetKind::Expense) {
+                let window = period_window(budget, date);
+                if budget.period == Period::Custom && !(window.0..=window.1).contains(&date) {
+                    continue;
+                }
+                let per_day = *rates.entry((i, window.0)).or_insert_with(|| {
+                    let recurring = expected_spending(&budget.category, window.0, window.1);
+                    budgeted_per_day(conn, budget, window, recurring, today)
+                });
+                balances[default] -= per_day;
+                day.outflow += per_day;
+            }
+        }
+
+        day.balances = balances.clone();
+        projected.push(day);
+    }
+
+    Forecast { accounts, opening, days: projected, expected }
+}
+
+/// Every projected day, flagging the accounts that end it below zero.
+pub fn daily_table(forecast: &Forecast) -> Table {
+    let mut columns = vec!["date", "inflow", "outflow"];
+    columns.extend(forecast.accounts.iter().map(|a| a.name.as_str()));
+    columns.extend(["events", "warning"]);
+
+    let mut table = Table::new("forecast", &columns).with_title("Projected daily balances");
+    for day in &forecast.days {
+        let negative: Vec<&str> = forecast
+            .accounts
+            .iter()
+            .zip(&day.balances)
+            .filter(|(_, b)| **b < 0.0)
+            .map(|(a, _)| a.name.as_str())
+            .collect();
+
+        let mut row: Vec<Value> = vec![day.date.to_string().into(), day.inflow.into(), day.outflow.into()];
+        row.extend(day.balances.iter().map(|b| Value::from(*b)));
+        row.push((!day.events.is_empty()).then(|| day.events.join("; ")).into());
+        row.push((!negative.is_empty()).then(|| format!("below zero: {}", negative.join(", "))).into());
+        table.push(row);
+    }
+    table
+}
+
+/// Per account: the lowest projected balance, the first day below zero and
+/// whether that comes before the next expected income.
+pub fn summary_table(forecast: &Forecast) -> Table {
+    let (Some(first), Some(last)) = (forecast.days.first(), forecast.days.last()) else {
+        return Table::new("forecast_summary", &["account"]);
+    };
+    let mut table = Table::new(
+        "forecast_summary",
+        &["account", "balance_now", "lowest", "lowest_date", "end_balance", "first_negative", "next_income", "status"],
+    )
+    .with_title(format!("Projection from {} to {}", first.date, last.date));
+
+    for (i, account) in forecast.accounts.iter().enumerate() {
+        let lowest = forecast.days.iter().min_by(|a, b| a.balances[i].total_cmp(&b.balances[i])).unwrap();
+        let first_negative = forecast.days.iter().find(|d| d.balances[i] < 0.0).map(|d| d.date);
+        let next_income = forecast
+            .expected
+            .iter()
+            .filter(|e| e.amount > 0.0 && e.account.as_ref().map_or(account.is_default, |a| *a == account.name))
+            .filter_map(|e| e.dates.first().copied())
+            .filter(|d| *d <= last.date)
+            .min();
+
+        let status = match (first_negative, next_income) {
+            (None, _) => "ok",
+            (Some(neg), Some(income)) if neg < income => "below zero before next income",
+            (Some(_), _) => "below zero",
+        };
+        table.push(vec![
+            account.name.as_str().into(),
+            forecast.opening[i].into(),
+            lowest.balances[i].into(),
+            lowest.date.to_string().into(),
+            last.balances[i].into(),
+            first_negative.map(|d| d.to_string()).into(),
+            next_income.map(|d| d.to_string()).into(),
+            status.into(),
+        ]);
+    }
+    table
+}
+
+pub fn recurring_table(forecast: &Forecast) -> Table {
+    let mut table = Table::new("recurring", &["description", "category", "account", "source", "amount", "next_date"])
+        .with_title("Recurring transactions in the forecast");
+    for e in &forecast.expected {
+        table.push(vec![
+            e.description.as_str().into(),
+            e.category.clone().into(),
+            e.account.clone().into(),
+            e.source.into(),
+            e.amount.into(),
+            e.dates.first().map(|d| d.to_string()).into(),
+        ]);
+    }
+    table
+}
@@ -4,58 +4,545 @@ mod parser;
 mod categorize;
 mod budget;
 mod reports;
+mod export;
+mod forecast;
+mod networth;
+mod recurring;
+mod stats;
+mod output;
 mod ui;
 pub mod models;
 
 
+use chrono::Datelike;
 use clap::Parser;
+use output::Table;
 
 fn main()  {
     let cli = cli::Cli::parse();
     let conn = db::init_db();
 
-    match cli.command {
-    cli::Commands::Add { amount, category, description, date } => {
-        db::add_transaction(&conn, amount, &category, &description, date);
+    // Scheduled transactions that fell due since the last run are posted
+    // before anything reads the data. An import posts them afterwards, so
+    // the bank's copies are matched rather than duplicated.
+    if !matches!(
+        cli.command,
+        cli::Commands::Recurring { action: cli::RecurringAction::Post { .. } } | cli::Commands::Import { .. }
+    ) {
+        post_scheduled(&conn);
+    }
+
+    let tables: Vec<Table> = match cli.command {
+    cli::Commands::Add { amount, category, description, date, account, notes } => {
+        check_account(&conn, account.as_deref());
+        let id = db::add_transaction(&conn, amount, &category, &description, date, account.as_deref());
+        if let Some(notes) = notes {
+            db::set_notes(&conn, id, &notes);
+        }
+        let saved = db::load_transaction(&conn, id).expect("Failed to load saved transaction");
+        with_alerts(&conn, vec![db::transactions_table("saved", &[saved]).with_title("Saved:")])
     }
     cli::Commands::Search { keyword } => {
-        db::search_transactions(&conn, keyword).unwrap();
+        match db::search_transactions(&conn, keyword) {
+            Ok(table) => vec![table],
+            Err(err) => {
+                eprintln!("Search failed: {}", err);
+                std::process::exit(1);
+            }
+        }
     }
-	cli::Commands::Import { file, r#type } => {
-		db::import_transactions(&conn, &file, &r#type);
-		categorize::categorize_transactions(&conn);
+	cli::Commands::Import { file, r#type, account, anomalies } => {
+		check_account(&conn, account.as_deref());
+		let last_id: i64 = conn
+			.query_row("SELECT COALESCE(MAX(id), 0) FROM transactions", [], |row| row.get(0))
+			.unwrap();
+		let imported = db::import_transactions(&conn, &file, &r#type, account.as_deref()).unwrap_or_else(|err| {
+			eprintln!("{}", err);
+			std::process::exit(1);
+		});
+		let categorized = categorize::categorize_transactions(&conn);
+		let replaced = recurring::replace_posted(&conn, last_id);
+		post_scheduled(&conn);
+
+		let mut summary = Table::new("import", &["imported", "categorized", "replaced_recurring"])
+			.with_title("Import completed.");
+		summary.push(vec![imported.into(), categorized.into(), replaced.into()]);
+		let mut tables = vec![summary];
+		if anomalies {
+			let today = chrono::Local::now().date_naive();
+			let found = reports::anomalies::find_anomalies(&conn, ANOMALY_THRESHOLD, today, |id, _| id > last_id);
+			tables.push(reports::anomalies::anomalies_table(&found, format!("{} unusual amount(s) in this import", found.len())));
+		}
+		with_alerts(&conn, tables)
     }
 	
-	cli::Commands::Budget { category, limit } => {
+	cli::Commands::Budget { action: Some(action), .. } => match action {
+		cli::BudgetAction::Ledger { category } => vec![or_exit(budget::ledger_table(&conn, &category))],
+		cli::BudgetAction::History { category } => vec![budget::history_table(&conn, &category)],
+		cli::BudgetAction::Envelopes { month } => {
+			budget::envelope::envelopes_tables(&conn, parse_month_arg(month))
+		}
+		cli::BudgetAction::Assign { category, amount, month } => {
+			let month = parse_month_arg(month);
+			if let Err(err) = budget::envelope::assign(&conn, &category, amount, month) {
+				eprintln!("{}", err);
+				std::process::exit(1);
+			}
+			budget::envelope::envelopes_tables(&conn, month)
+		}
+		cli::BudgetAction::Move { from, to, amount, month } => {
+			let month = parse_month_arg(month);
+			if let Err(err) = budget::envelope::move_money(&conn, &from, &to, amount, month) {
+				eprintln!("{}", err);
+				std::process::exit(1);
+			}
+			budget::envelope::envelopes_tables(&conn, month)
+		}
+		cli::BudgetAction::Suggest { months, method, percentile, apply, save_template } => {
+			let today = chrono::Local::now().date_naive();
+			let suggestions = budget::template::suggest(&conn, months, method, percentile, today);
+			let basis = match method {
+				models::budget::SuggestMethod::Percentile => format!("{}th percentile", percentile),
+				other => other.as_str().to_string(),
+			};
+			let table = budget::template::suggestions_table(&suggestions, format!(
+				"Suggested limits ({} of monthly spending, {})",
+				basis,
+				budget::template::window_label(months, today)
+			));
+
+			let budgets = budget::template::suggested_budgets(suggestions);
+			if apply {
+				budget::template::apply_budgets(&conn, &budgets, today);
+				vec![table, or_exit(budget::check_all(&conn, today)).with_title("Budgets updated:")]
+			} else if let Some(name) = save_template {
+				budget::template::save_template(&conn, &name, &budgets);
+				vec![table.with_title(format!("Saved as template '{}':", name))]
+			} else {
+				vec![table]
+			}
+		}
+		cli::BudgetAction::SaveTemplate { name } => {
+			let budgets = db::load_budgets(&conn).unwrap();
+			budget::template::save_template(&conn, &name, &budgets);
+			vec![budget::template::templates_table(&conn)]
+		}
+		cli::BudgetAction::ApplyTemplate { name, replace } => {
+			let today = chrono::Local::now().date_naive();
+			if budget::template::apply_template(&conn, &name, replace, today).is_none() {
+				eprintln!("No template named '{}'.", name);
+				std::process::exit(1);
+			}
+			vec![or_exit(budget::check_all(&conn, today)).with_title(format!("Applied template '{}':", name))]
+		}
+		cli::BudgetAction::Templates => vec![budget::template::templates_table(&conn)],
+	},
+
+	cli::Commands::Budget {
+		action: None, category, limit, kind, period, start, end, rollover, rollover_cap, thresholds, effective_from, as_of,
+	} => {
+		let today = chrono::Local::now().date_naive();
+		let as_of = as_of.map_or(today, |d| parse_date_arg(&d));
+
 		match (category, limit) {
-			(Some(cat), Some(lim)) => budget::set_budget(&conn, &cat, lim),
-			(Some(cat), None) => budget::check_budget(&conn, &cat),
-			(None, None) => budget::check_all(&conn),
-			_ => println!("Invalid usage."),
+			(Some(cat), Some(lim)) => {
+				// Changing an existing budget only touches the options given.
+				let existing = db::load_budget(&conn, &cat).ok();
+				let start = start.map(|s| parse_date_arg(&s)).or_else(|| existing.as_ref().and_then(|b| b.start_date));
+				let end = end.map(|s| parse_date_arg(&s)).or_else(|| existing.as_ref().and_then(|b| b.end_date));
+				let period = period.or(existing.as_ref().map(|b| b.period)).unwrap_or(models::budget::Period::Monthly);
+				let rollover_cap = match rollover_cap.as_deref().map(str::trim) {
+					None => existing.as_ref().and_then(|b| b.rollover_cap),
+					Some(cap) if cap.eq_ignore_ascii_case("none") => None,
+					Some(cap) => match cap.parse::<f64>() {
+						Ok(cap) if cap.is_finite() && cap >= 0.0 => Some(cap),
+						_ => {
+							eprintln!("Invalid --rollover-cap '{}'. Use an amount or 'none'.", cap);
+							std::process::exit(1);
+						}
+					},
+				};
+				if period == models::budget::Period::Custom && (start.is_none() || end.is_none()) {
+					eprintln!("A custom budget needs both --start and --end.");
+					std::process::exit(1);
+				}
+				let new_budget = models::budget::Budget {
+					category: cat.clone(),
+					limit_amount: lim,
+					kind: kind.or(existing.as_ref().map(|b| b.kind)).unwrap_or(models::budget::BudgetKind::Expense),
+					period,
+					start_date: start,
+					end_date: end,
+					rollover: rollover.or(existing.as_ref().map(|b| b.rollover)).unwrap_or(models::budget::Rollover::None),
+					rollover_cap,
+					thresholds: match (thresholds, existing) {
+						(Some(t), _) => models::budget::normalize_thresholds(t),
+						(None, Some(b)) => b.thresholds,
+						(None, None) => models::budget::DEFAULT_THRESHOLDS.to_vec(),
+					},
+				};
+				let effective_from = effective_from
+					.map(|d| parse_date_arg(&d))
+					.unwrap_or_else(|| budget::period_window(&new_budget, today).0);
+
+				budget::set_budget(&conn, &new_budget, effective_from);
+				vec![or_exit(budget::check_budget(&conn, &cat, today)).with_title("Budget saved:")]
+			}
+			(Some(cat), None) => vec![or_exit(budget::check_budget(&conn, &cat, as_of))],
+			(None, None) => {
+				let mut tables = vec![or_exit(budget::check_all(&conn, as_of))];
+				tables.extend(or_exit(budget::check_targets(&conn, as_of)));
+				tables
+			}
+			(None, Some(_)) => {
+				eprintln!("--limit needs a --category.");
+				std::process::exit(1);
+			}
 		}
 		
 	}
 	
-	cli::Commands::Reports { month, year } => {
-    let month = month.unwrap_or_else(|| chrono::Local::now().format("%m").to_string());
-    let year  = year.unwrap_or_else(|| chrono::Local::now().format("%Y").to_string());
+	cli::Commands::Reports { action: Some(action), .. } => match action {
+		cli::ReportAction::Compare { period, against, top } => {
+			let today = chrono::Local::now().date_naive();
+			let base = match period {
+				Some(p) => parse_period_arg(&p),
+				None => reports::range::DateRange::month(today.year(), today.month()).unwrap(),
+			};
+			let mut others: Vec<_> = if against.is_empty() {
+				vec![base.earlier(base.months()), base.earlier(12)]
+			} else {
+				against.iter().map(|p| parse_period_arg(p)).collect()
+			};
+			// For a whole year both defaults are the previous year.
+			others.dedup();
+			reports::compare::compare(&conn, base, &others, top)
+		}
+		cli::ReportAction::Anomalies { days, threshold } => {
+			let today = chrono::Local::now().date_naive();
+			let from = today - chrono::Days::new(days as u64);
+			let found = reports::anomalies::find_anomalies(&conn, threshold, today, |_, date| date > from);
+			vec![reports::anomalies::anomalies_table(&found, format!(
+				"{} unusual amount(s) since {}",
+				found.len(),
+				from + chrono::Days::new(1)
+			))]
+		}
+		cli::ReportAction::Pivot { rows, cols, value, filter, range } => {
+			let range = reports::range::resolve(&conn, &range.spec(), chrono::Local::now().date_naive()).unwrap_or_else(|err| {
+				eprintln!("{}", err);
+				std::process::exit(1);
+			});
+			let filters: Vec<_> = filter
+				.iter()
+				.map(|f| {
+					reports::pivot::Filter::parse(f).unwrap_or_else(|| {
+						eprintln!(
+							"Invalid filter '{}'. Use FIELD=VALUE[,VALUE...], FIELD!=VALUE, FIELD~TEXT or amount<N (also <=, >, >=, =).",
+							f
+						);
+						std::process::exit(1);
+					})
+				})
+				.collect();
+			vec![reports::pivot::pivot(&conn, range, rows, cols, value, &filters)]
+		}
+		cli::ReportAction::Trend { months } => {
+			vec![reports::trend::trend(&conn, months, chrono::Local::now().date_naive())]
+		}
+	},
+
+	cli::Commands::Reports { action: None, range, group_by, top } => {
+    let today = chrono::Local::now().date_naive();
+    let range = reports::range::resolve(&conn, &range.spec(), today).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
 
-    let total = reports::monthly_spending(&conn, &month, &year);
-    println!("Total spending for {}/{}: {} lei", month, year, total);
+    let categories = reports::category_totals(&conn, range);
+    let totals = reports::Totals::from_categories(&categories);
 
-    reports::category_breakdown(&conn, &month, &year);
+    let mut tables = vec![reports::summary_table(range, &totals)];
+    tables.extend(reports::category_breakdown(&categories, range));
+    if let Some(group_by) = group_by {
+        tables.push(reports::series::time_series(&conn, range, group_by));
+    }
+    if let Some(top) = top {
+        tables.extend(reports::distribution::distribution(&conn, range, top));
     }
 
-	
-	cli::Commands::Sql { query } => {
-    db::run_sql(&conn, &query);
+    // Budgets are evaluated as of the range's last day, with the limits in force then.
+    tables.push(or_exit(budget::check_all(&conn, range.to)).with_title(format!("Budgets as of {}", range.to)));
+    tables.extend(or_exit(budget::check_targets(&conn, range.to)));
+    tables
     }
+
+	
+	cli::Commands::Forecast { days } => {
+		let today = chrono::Local::now().date_naive();
+		let accounts = db::load_accounts(&conn).unwrap();
+		let budgets = db::load_budgets(&conn).unwrap();
+		let rules = db::load_rules(&conn).unwrap();
+		let forecast = forecast::forecast(&conn, accounts, &rules, &budgets, days, today);
+		if forecast.accounts.is_empty() {
+			eprintln!("No asset account is computed from transactions, so there is nothing to project.");
+		}
+		vec![
+			forecast::recurring_table(&forecast),
+			forecast::daily_table(&forecast),
+			forecast::summary_table(&forecast),
+		]
+	}
+
+	cli::Commands::Recurring { action } => {
+		let today = chrono::Local::now().date_naive();
+		match action {
+			cli::RecurringAction::Add { amount, category, description, account, rule, start, end, no_auto } => {
+				check_account(&conn, account.as_deref());
+				let start_date = start.map_or(today, |d| parse_date_arg(&d));
+				let schedule = models::recurring::Schedule::parse(&rule, start_date).unwrap_or_else(|| {
+					eprintln!(
+						"Invalid rule '{}'. Use monthly[:DAY|last], last-business-day, quarterly[:DAY], yearly, weekly, biweekly or every:N(d|w|m).",
+						rule
+					);
+					std::process::exit(1);
+				});
+				let mut new_rule = models::recurring::RecurringRule {
+					id: 0,
+					amount,
+					category,
+					description,
+					account,
+					rule: rule.trim().to_lowercase(),
+					schedule,
+					start_date,
+					end_date: end.map(|d| parse_date_arg(&d)),
+					auto_post: !no_auto,
+				};
+				new_rule.id = recurring::add_rule(&conn, &new_rule);
+
+				let mut tables = Vec::new();
+				if new_rule.auto_post {
+					let postings = recurring::post_due(&conn, std::slice::from_ref(&new_rule), today, false);
+					if !postings.is_empty() {
+						tables.push(recurring::postings_table(&postings, false));
+					}
+				}
+				tables.insert(0, recurring::rules_table(&conn, &[new_rule], today).with_title("Saved:"));
+				with_alerts(&conn, tables)
+			}
+			cli::RecurringAction::List => {
+				vec![recurring::rules_table(&conn, &db::load_rules(&conn).unwrap(), today)]
+			}
+			cli::RecurringAction::Remove { id } => {
+				if !recurring::remove_rule(&conn, id) {
+					eprintln!("No recurring transaction with id {}.", id);
+					std::process::exit(1);
+				}
+				vec![recurring::rules_table(&conn, &db::load_rules(&conn).unwrap(), today)]
+			}
+			cli::RecurringAction::Post { dry_run } => {
+				let rules = db::load_rules(&conn).unwrap();
+				let table = recurring::postings_table(&recurring::post_due(&conn, &rules, today, dry_run), dry_run);
+				if dry_run { vec![table] } else { with_alerts(&conn, vec![table]) }
+			}
+		}
+	}
+
+	cli::Commands::Subscriptions => {
+		let today = chrono::Local::now().date_naive();
+		let subscriptions = recurring::subscriptions::subscriptions(&conn);
+		let mut tables = vec![recurring::subscriptions::subscriptions_table(&subscriptions, today)];
+		let issues = recurring::subscriptions::issues_table(&subscriptions, today);
+		if !issues.rows.is_empty() {
+			tables.push(issues);
+		}
+		tables
+	}
+
+	cli::Commands::Networth { action: Some(action), .. } => match action {
+		cli::NetworthAction::Account { name, kind, opening, manual, default } => {
+			let account = models::account::Account { name, kind, opening_balance: opening, manual, is_default: default };
+			networth::set_account(&conn, &account);
+			if account.manual && account.opening_balance != 0.0 {
+				networth::record_snapshot(&conn, &account.name, chrono::Local::now().date_naive(), account.opening_balance);
+			}
+			let accounts = db::load_accounts(&conn).unwrap();
+			vec![networth::accounts_table(&conn, &accounts, chrono::Local::now().date_naive())]
+		}
+		cli::NetworthAction::Snapshot { account, balance, date } => {
+			match db::load_account(&conn, &account) {
+				Ok(a) if a.manual => {}
+				Ok(_) => {
+					eprintln!("'{}' is computed from transactions; set its --opening balance instead.", account);
+					std::process::exit(1);
+				}
+				Err(_) => {
+					eprintln!("No account named '{}'. Create it with `networth account {} --manual`.", account, account);
+					std::process::exit(1);
+				}
+			}
+			let date = date.map_or_else(|| chrono::Local::now().date_naive(), |d| parse_date_arg(&d));
+			networth::record_snapshot(&conn, &account, date, balance);
+			let accounts = db::load_accounts(&conn).unwrap();
+			vec![networth::accounts_table(&conn, &accounts, date)]
+		}
+		cli::NetworthAction::Accounts { as_of } => {
+			let date = as_of.map_or_else(|| chrono::Local::now().date_naive(), |d| parse_date_arg(&d));
+			let accounts = db::load_accounts(&conn).unwrap();
+			vec![networth::accounts_table(&conn, &accounts, date)]
+		}
+	},
+
+	cli::Commands::Networth { action: None, months } => {
+		let today = chrono::Local::now().date_naive();
+		let accounts = db::load_accounts(&conn).unwrap();
+		if accounts.is_empty() {
+			eprintln!("No accounts yet. Add one with `networth account <name>`.");
+		}
+		vec![
+			networth::history_table(&conn, &accounts, months, today),
+			networth::accounts_table(&conn, &accounts, today),
+		]
+	}
+
+	cli::Commands::Alerts { all, ack } => {
+		if let Some(ids) = ack {
+			let count = budget::alerts::acknowledge(&conn, &ids);
+			eprintln!("Acknowledged {} alert(s).", count);
+		}
+		vec![budget::alerts::list_alerts(&conn, all)]
+	}
+
+	cli::Commands::Export { keyword, r#type, output } => {
+		match export::export_transactions(&conn, keyword, &r#type, output.as_deref()) {
+			// With no file the export itself went to stdout; don't mix a summary into it.
+			Ok(count) => match output {
+				Some(path) => {
+					let mut summary = Table::new("export", &["exported", "file"])
+						.with_title("Export completed.");
+					summary.push(vec![count.into(), path.into()]);
+					vec![summary]
+				}
+				None => Vec::new(),
+			},
+			Err(err) => {
+				eprintln!("{}", err);
+				std::process::exit(1);
+			}
+		}
+	}
+
+	cli::Commands::Sql { query, write, save, run, list } => {
+		if list {
+			vec![db::saved_queries(&conn).expect("Failed to load saved queries")]
+		} else {
+			let query = match (query, run) {
+				(Some(query), _) => query,
+				(None, Some(name)) => db::load_query(&conn, &name).unwrap_or_else(|_| {
+					eprintln!("No saved query named '{}'.", name);
+					std::process::exit(1);
+				}),
+				(None, None) => unreachable!("clap requires --query, --run or --list"),
+			};
+
+			let read_only;
+			let sql_conn = if write {
+				&conn
+			} else {
+				read_only = db::open_read_only();
+				&read_only
+			};
+
+			match db::run_sql(sql_conn, &query) {
+				// Only queries that ran are worth saving.
+				Ok(table) => {
+					if let Some(name) = save {
+						db::save_query(&conn, &name, &query).expect("Failed to save query");
+					}
+					vec![table]
+				}
+				Err(err) => {
+					eprintln!("Query failed: {}", err);
+					if let rusqlite::Error::SqliteFailure(e, _) = &err
+						&& e.code == rusqlite::ErrorCode::ReadOnly
+					{
+						eprintln!("The sql command is read-only; pass --write to modify data.");
+					}
+					std::process::exit(1);
+				}
+			}
+		}
+	}
 	
 	cli::Commands::Tui => { ui::run_tui(&conn).expect("Failed to launch TUI");
+		Vec::new()
 	}
+    };
 
+    output::print(&tables, cli.format);
+}
 
-    _ => println!("Feature not implemented yet"),
+
+/// The `reports anomalies` default, used when checking imports.
+const ANOMALY_THRESHOLD: f64 = 3.5;
+
+fn parse_date_arg(date: &str) -> chrono::NaiveDate {
+    parser::parse_date(date).unwrap_or_else(|| {
+        eprintln!("Invalid date '{}'. Use MM/DD/YYYY or YYYY-MM-DD.", date);
+        std::process::exit(1);
+    })
+}
+
+fn parse_period_arg(period: &str) -> reports::range::DateRange {
+    reports::range::DateRange::parse(period).unwrap_or_else(|| {
+        eprintln!("Invalid period '{}'. Use YYYY-MM, YYYYQn or YYYY.", period);
+        std::process::exit(1);
+    })
+}
+
+fn parse_month_arg(month: Option<String>) -> chrono::NaiveDate {
+    match month {
+        Some(m) => budget::envelope::parse_month(&m).unwrap_or_else(|| {
+            eprintln!("Invalid month '{}'. Use YYYY-MM.", m);
+            std::process::exit(1);
+        }),
+        None => budget::envelope::month_start(chrono::Local::now().date_naive()),
+    }
+}
+
+fn check_account(conn: &rusqlite::Connection, account: Option<&str>) {
+    if let Some(name) = account
+        && db::load_account(conn, name).is_err()
+    {
+        eprintln!("No account named '{}'. Create it with `networth account {}`.", name, name);
+        std::process::exit(1);
+    }
+}
+
+/// Posts the due instances of every automatic recurring transaction.
+fn post_scheduled(conn: &rusqlite::Connection) {
+    let rules: Vec<_> = db::load_rules(conn).unwrap().into_iter().filter(|r| r.auto_post).collect();
+    let postings = recurring::post_due(conn, &rules, chrono::Local::now().date_naive(), false);
+    let posted = postings.iter().filter(|p| !p.matched).count();
+    if posted > 0 {
+        eprintln!("Posted {} recurring transaction(s) that fell due.", posted);
     }
+}
+
+fn or_exit<T>(result: Result<T, budget::BudgetError>) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    })
+}
 
+// Budget thresholds are re-checked whenever transactions are added, and any
+// newly crossed ones are reported along with the command's own output.
+fn with_alerts(conn: &rusqlite::Connection, mut tables: Vec<Table>) -> Vec<Table> {
+    let fired = budget::alerts::evaluate(conn, chrono::Local::now().date_naive());
+    if !fired.rows.is_empty() {
+        tables.push(fired);
+    }
+    tables
 }
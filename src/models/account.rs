@@ -0,0 +1,34 @@
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AccountKind {
+    /// Something you own: a bank account, investments, property
+    Asset,
+    /// Something you owe: a credit card, a loan
+    Liability,
+}
+
+impl AccountKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountKind::Asset => "asset",
+            AccountKind::Liability => "liability",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        AccountKind::from_str(s, true).ok()
+    }
+}
+
+#[derive(Debug)]
+pub struct Account {
+    pub name: String,
+    pub kind: AccountKind,
+    /// Balance before the first transaction (the amount owed for liabilities)
+    pub opening_balance: f64,
+    /// Balances are entered as snapshots instead of computed from transactions
+    pub manual: bool,
+    /// Transactions without an account belong to this one
+    pub is_default: bool,
+}
@@ -1,5 +1,131 @@
+use chrono::NaiveDate;
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Period {
+    Weekly,
+    Monthly,
+    Quarterly,
+    Yearly,
+    /// A single fixed range between a start and an end date
+    Custom,
+}
+
+impl Period {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Period::Weekly => "weekly",
+            Period::Monthly => "monthly",
+            Period::Quarterly => "quarterly",
+            Period::Yearly => "yearly",
+            Period::Custom => "custom",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Period::from_str(s, true).ok()
+    }
+}
+
+/// What happens to the unspent (or overspent) amount at the end of a period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Rollover {
+    None,
+    /// Unspent money is added to the next period
+    Surplus,
+    /// Unspent money is added to, and overspending taken from, the next period
+    SurplusAndDeficit,
+}
+
+impl Rollover {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rollover::None => "none",
+            Rollover::Surplus => "surplus",
+            Rollover::SurplusAndDeficit => "surplus-and-deficit",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Rollover::from_str(s, true).ok()
+    }
+}
+
+/// What a budget tracks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BudgetKind {
+    /// A spending limit for a category
+    Expense,
+    /// Income expected in a category, e.g. a salary
+    Income,
+    /// A target savings rate, in percent of income
+    Savings,
+}
+
+impl BudgetKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetKind::Expense => "expense",
+            BudgetKind::Income => "income",
+            BudgetKind::Savings => "savings",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        BudgetKind::from_str(s, true).ok()
+    }
+}
+
 #[derive(Debug)]
 pub struct Budget {
     pub category: String,
+    /// The spending limit, the expected income, or the target savings rate
     pub limit_amount: f64,
+    pub kind: BudgetKind,
+    pub period: Period,
+    /// Anchor for recurring periods, or the first day of a custom range
+    pub start_date: Option<NaiveDate>,
+    /// Last day of a custom range
+    pub end_date: Option<NaiveDate>,
+    pub rollover: Rollover,
+    /// Upper bound on the surplus carried into a period
+    pub rollover_cap: Option<f64>,
+    /// Percentages of the limit that raise an alert, in ascending order
+    pub thresholds: Vec<u32>,
+}
+
+pub const DEFAULT_THRESHOLDS: [u32; 2] = [80, 100];
+
+pub fn normalize_thresholds(mut thresholds: Vec<u32>) -> Vec<u32> {
+    thresholds.sort_unstable();
+    thresholds.dedup();
+    thresholds
+}
+
+/// Parses a stored `"50,75,100"` threshold list.
+pub fn parse_thresholds(text: &str) -> Vec<u32> {
+    normalize_thresholds(text.split(',').filter_map(|t| t.trim().parse().ok()).collect())
+}
+
+pub fn format_thresholds(thresholds: &[u32]) -> String {
+    thresholds.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(",")
+}
+
+/// How `budget suggest` turns past monthly spending into a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SuggestMethod {
+    Median,
+    Average,
+    /// The `--percentile` of monthly spending, e.g. the 90th
+    Percentile,
+}
+
+impl SuggestMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuggestMethod::Median => "median",
+            SuggestMethod::Average => "average",
+            SuggestMethod::Percentile => "percentile",
+        }
+    }
 }
@@ -1,2 +1,4 @@
 pub mod transaction;
 pub mod budget;
+pub mod account;
+pub mod recurring;
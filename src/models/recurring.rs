@@ -0,0 +1,185 @@
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+
+/// The day of the month a monthly rule falls on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonthDay {
+    /// A day of the month; in shorter months the last day is used instead
+    Day(u32),
+    Last,
+    /// The last Monday to Friday of the month
+    LastBusinessDay,
+}
+
+/// When a recurring transaction is due, counted from the rule's start date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    EveryDays(u32),
+    EveryWeeks(u32),
+    EveryMonths(u32, MonthDay),
+}
+
+impl Schedule {
+    /// Parses `monthly[:DAY|last]`, `last-business-day`, `quarterly[:DAY]`,
+    /// `yearly`, `weekly`, `biweekly` or `every:N(d|w|m)`. Monthly rules
+    /// without a day use the start date's day.
+    pub fn parse(text: &str, start: NaiveDate) -> Option<Self> {
+        let text = text.trim().to_lowercase();
+        let (name, arg) = match text.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (text.as_str(), None),
+        };
+        let month_day = |arg: Option<&str>| match arg {
+            None => Some(MonthDay::Day(start.day())),
+            Some("last") => Some(MonthDay::Last),
+            Some(day) => day.parse().ok().filter(|d| (1..=31).contains(d)).map(MonthDay::Day),
+        };
+
+        match (name, arg) {
+            ("monthly", arg) => month_day(arg).map(|d| Schedule::EveryMonths(1, d)),
+            ("quarterly", arg) => month_day(arg).map(|d| Schedule::EveryMonths(3, d)),
+            ("yearly", None) => Some(Schedule::EveryMonths(12, MonthDay::Day(start.day()))),
+            ("last-business-day", None) => Some(Schedule::EveryMonths(1, MonthDay::LastBusinessDay)),
+            ("weekly", None) => Some(Schedule::EveryWeeks(1)),
+            ("biweekly", None) => Some(Schedule::EveryWeeks(2)),
+            ("every", Some(arg)) => {
+                let unit = arg.chars().last()?;
+                let n: u32 = arg[..arg.len() - unit.len_utf8()].parse().ok().filter(|n| *n > 0)?;
+                match unit {
+                    'd' => Some(Schedule::EveryDays(n)),
+                    'w' => Some(Schedule::EveryWeeks(n)),
+                    'm' => Some(Schedule::EveryMonths(n, MonthDay::Day(start.day()))),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Due dates from `start` up to and including `to`.
+    pub fn occurrences(&self, start: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        let nth = |n: u32| -> NaiveDate {
+            match self {
+                Schedule::EveryDays(k) => start + Days::new((n * k) as u64),
+                Schedule::EveryWeeks(k) => start + Days::new((n * k * 7) as u64),
+                Schedule::EveryMonths(k, day) => {
+                    let month = start.with_day(1).unwrap() + Months::new(n * k);
+                    on_day(month, *day)
+                }
+            }
+        };
+        // A monthly day before the start date's day falls in the next month.
+        (0..).map(nth).skip_while(|d| *d < start).take_while(|d| *d <= to).collect()
+    }
+}
+
+fn last_of_month(first: NaiveDate) -> NaiveDate {
+    first + Months::new(1) - Days::new(1)
+}
+
+fn on_day(first: NaiveDate, day: MonthDay) -> NaiveDate {
+    let last = last_of_month(first);
+    match day {
+        MonthDay::Day(d) => first.with_day(d).unwrap_or(last),
+        MonthDay::Last => last,
+        MonthDay::LastBusinessDay => match last.weekday() {
+            Weekday::Sat => last - Days::new(1),
+            Weekday::Sun => last - Days::new(2),
+            _ => last,
+        },
+    }
+}
+
+#[derive(Debug)]
+pub struct RecurringRule {
+    pub id: i64,
+    pub amount: f64,
+    pub category: String,
+    pub description: String,
+    pub account: Option<String>,
+    /// The rule as entered, e.g. `monthly:1`
+    pub rule: String,
+    pub schedule: Schedule,
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>,
+    /// Posted automatically whenever the program starts
+    pub auto_post: bool,
+}
+
+impl RecurringRule {
+    /// Due dates up to `to`, stopping at the end date.
+    pub fn due_dates(&self, to: NaiveDate) -> Vec<NaiveDate> {
+        let to = self.end_date.map_or(to, |end| end.min(to));
+        self.schedule.occurrences(self.start_date, to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn parses_rules() {
+        let start = date(2024, 1, 20);
+        assert_eq!(Schedule::parse("monthly", start), Some(Schedule::EveryMonths(1, MonthDay::Day(20))));
+        assert_eq!(Schedule::parse("Monthly:last", start), Some(Schedule::EveryMonths(1, MonthDay::Last)));
+        assert_eq!(Schedule::parse("quarterly:5", start), Some(Schedule::EveryMonths(3, MonthDay::Day(5))));
+        assert_eq!(Schedule::parse("last-business-day", start), Some(Schedule::EveryMonths(1, MonthDay::LastBusinessDay)));
+        assert_eq!(Schedule::parse("every:10d", start), Some(Schedule::EveryDays(10)));
+        assert_eq!(Schedule::parse("every:2w", start), Some(Schedule::EveryWeeks(2)));
+        assert_eq!(Schedule::parse("every:6m", start), Some(Schedule::EveryMonths(6, MonthDay::Day(20))));
+    }
+
+    #[test]
+    fn rejects_bad_rules() {
+        let start = date(2024, 1, 20);
+        for rule in ["monthly:32", "monthly:0", "every:0d", "every:3y", "every:d", "weekly:2", "daily"] {
+            assert_eq!(Schedule::parse(rule, start), None, "{}", rule);
+        }
+    }
+
+    #[test]
+    fn month_end_days_fall_back_to_the_last_day() {
+        let start = date(2024, 1, 31);
+        let schedule = Schedule::parse("monthly", start).unwrap();
+        assert_eq!(
+            schedule.occurrences(start, date(2024, 4, 30)),
+            vec![date(2024, 1, 31), date(2024, 2, 29), date(2024, 3, 31), date(2024, 4, 30)]
+        );
+    }
+
+    #[test]
+    fn a_day_before_the_start_begins_next_month() {
+        let start = date(2024, 1, 20);
+        let schedule = Schedule::parse("monthly:1", start).unwrap();
+        assert_eq!(schedule.occurrences(start, date(2024, 3, 31)), vec![date(2024, 2, 1), date(2024, 3, 1)]);
+    }
+
+    #[test]
+    fn last_business_day_skips_weekends() {
+        let start = date(2024, 8, 1);
+        let schedule = Schedule::parse("last-business-day", start).unwrap();
+        // August 31st 2024 is a Saturday, November 30th a Saturday, June 30th 2024 a Sunday.
+        assert_eq!(
+            schedule.occurrences(start, date(2024, 11, 30)),
+            vec![date(2024, 8, 30), date(2024, 9, 30), date(2024, 10, 31), date(2024, 11, 29)]
+        );
+        let june = date(2024, 6, 1);
+        assert_eq!(schedule.occurrences(june, date(2024, 6, 30)), vec![date(2024, 6, 28)]);
+    }
+
+    #[test]
+    fn every_n_days_and_weeks() {
+        let start = date(2024, 2, 25);
+        let days = Schedule::parse("every:3d", start).unwrap();
+        assert_eq!(
+            days.occurrences(start, date(2024, 3, 4)),
+            vec![date(2024, 2, 25), date(2024, 2, 28), date(2024, 3, 2)]
+        );
+        let weeks = Schedule::parse("every:2w", start).unwrap();
+        assert_eq!(weeks.occurrences(start, date(2024, 3, 24)), vec![date(2024, 2, 25), date(2024, 3, 10), date(2024, 3, 24)]);
+    }
+}
@@ -1,8 +1,12 @@
-#[derive(Debug)]
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
 pub struct Transaction {
     pub id: i32,
     pub amount: f64,
     pub category: Option<String>,
     pub description: Option<String>,
     pub date: String,
+    pub account: Option<String>,
+    pub notes: Option<String>,
 }
@@ -0,0 +1,144 @@
+use chrono::{Days, Months, NaiveDate};
+use rusqlite::Connection;
+use crate::db::ISO_DATE;
+use crate::models::account::{Account, AccountKind};
+use crate::output::Table;
+use crate::reports::series::GroupBy;
+
+/// Creates or updates an account. The first account that tracks
+/// transactions becomes the default one unless another is already set.
+pub fn set_account(conn: &Connection, account: &Account) {
+    let has_default: bool = conn
+        .query_row("SELECT EXISTS (SELECT 1 FROM accounts WHERE is_default AND name <> ?1)", [&account.name], |row| row.get(0))
+        .unwrap();
+    let is_default = account.is_default || (!account.manual && !has_default);
+
+    if is_default {
+        conn.execute("UPDATE accounts SET is_default = 0", []).expect("Failed to update accounts");
+    }
+    conn.execute(
+        "INSERT INTO accounts (name, kind, opening_balance, manual, is_default)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(name) DO UPDATE SET
+             kind = excluded.kind,
+             opening_balance = excluded.opening_balance,
+             manual = excluded.manual,
+             is_default = excluded.is_default",
+        (&account.name, account.kind.as_str(), account.opening_balance, account.manual, is_default),
+    ).expect("Failed to save account");
+}
+
+pub fn record_snapshot(conn: &Connection, account: &str, date: NaiveDate, balance: f64) {
+    conn.execute(
+        "INSERT INTO balance_snapshots (account, date, balance) VALUES (?1, ?2, ?3)
+         ON CONFLICT(account, date) DO UPDATE SET balance = excluded.balance",
+        (account, date.to_string(), balance),
+    ).expect("Failed to record balance");
+}
+
+
+/// An account's balance at the end of `date`. Manual accounts use their
+/// latest snapshot; the others add up their transactions. Liabilities are
+/// returned as the (positive) amount owed.
+pub fn balance_on(conn: &Connection, account: &Account, date: NaiveDate) -> f64 {
+    if account.manual {
+        return conn
+            .query_row(
+                "SELECT balance FROM balance_snapshots
+                 WHERE account = ?1 AND date <= ?2
+                 ORDER BY date DESC LIMIT 1",
+                [&account.name, &date.to_string()],
+                |row| row.get(0),
+            )
+            .unwrap_or(0.0);
+    }
+
+    let total: f64 = conn
+        .query_row(
+            &format!(
+                "SELECT COALESCE(SUM(amount), 0) FROM transactions
+                 WHERE (account = ?1 OR (account IS NULL AND ?2))
+                 AND {date} <= ?3",
+                date = ISO_DATE
+            ),
+            (&account.name, account.is_default, date.to_string()),
+            |row| row.get(0),
+        )
+        .unwrap();
+
+    match account.kind {
+        AccountKind::Asset => account.opening_balance + total,
+        AccountKind::Liability => account.opening_balance - total,
+    }
+}
+
+/// Total assets and liabilities at the end of `date`.
+pub fn net_worth_on(conn: &Connection, accounts: &[Account], date: NaiveDate) -> (f64, f64) {
+    let mut assets = 0.0;
+    let mut liabilities = 0.0;
+    for account in accounts {
+        let balance = balance_on(conn, account, date);
+        match account.kind {
+            AccountKind::Asset => assets += balance,
+            AccountKind::Liability => liabilities += balance,
+        }
+    }
+    (assets, liabilities)
+}
+
+
+const BAR_WIDTH: f64 = 30.0;
+
+/// Net worth at each month-end over the last `months` months; the current
+/// month is taken as of `today`.
+pub fn history_table(conn: &Connection, accounts: &[Account], months: u32, today: NaiveDate) -> Table {
+    let current = GroupBy::Month.bucket_start(today);
+    let mut rows = Vec::new();
+    for i in (0..months.max(1)).rev() {
+        let start = current - Months::new(i);
+        let end = (start + Months::new(1) - Days::new(1)).min(today);
+        let (assets, liabilities) = net_worth_on(conn, accounts, end);
+        rows.push((start, end, assets, liabilities));
+    }
+
+    let widest = rows.iter().map(|r| (r.2 - r.3).abs()).fold(0.0, f64::max);
+    let mut table = Table::new("networth", &["month", "date", "assets", "liabilities", "net_worth", "change", "chart"])
+        .with_title("Net worth at month-end");
+    let mut previous = None;
+    for (start, end, assets, liabilities) in rows {
+        let net = assets - liabilities;
+        let bar = if widest > 0.0 { (net.abs() / widest * BAR_WIDTH).round() as usize } else { 0 };
+        let chart = if net < 0.0 { format!("-{}", "█".repeat(bar)) } else { "█".repeat(bar) };
+
+        table.push(vec![
+            GroupBy::Month.label(start).into(),
+            end.to_string().into(),
+            assets.into(),
+            liabilities.into(),
+            net.into(),
+            previous.map(|p| net - p).into(),
+            chart.into(),
+        ]);
+        previous = Some(net);
+    }
+    table
+}
+
+pub fn accounts_table(conn: &Connection, accounts: &[Account], date: NaiveDate) -> Table {
+    let mut table = Table::new("accounts", &["account", "kind", "source", "balance"])
+        .with_title(format!("Balances on {}", date));
+    for account in accounts {
+        let source = match (account.manual, account.is_default) {
+            (true, _) => "snapshots",
+            (false, true) => "transactions (default)",
+            (false, false) => "transactions",
+        };
+        table.push(vec![
+            account.name.as_str().into(),
+            account.kind.as_str().into(),
+            source.into(),
+            balance_on(conn, account, date).into(),
+        ]);
+    }
+    table
+}
@@ -0,0 +1,281 @@
+use std::io::{self, Write};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{Map, Value as JsonValue};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+    Tsv,
+}
+
+/// A single typed cell of command output.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Null,
+    Int(i64),
+    Real(f64),
+    Text(String),
+}
+
+impl Value {
+    fn to_plain(&self) -> String {
+        match self {
+            Value::Null => String::new(),
+            Value::Int(i) => i.to_string(),
+            Value::Real(r) => format!("{:.2}", r),
+            Value::Text(t) => t.clone(),
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(self, Value::Int(_) | Value::Real(_))
+    }
+}
+
+impl From<i32> for Value {
+    fn from(v: i32) -> Self {
+        Value::Int(v as i64)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::Int(v)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(v: usize) -> Self {
+        Value::Int(v as i64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Real(v)
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Text(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Text(v.to_string())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Self {
+        v.map(Into::into).unwrap_or(Value::Null)
+    }
+}
+
+/// The structured result of a command: named columns and typed rows.
+///
+/// `name` is the machine-readable key used in JSON output; `title` is the
+/// heading shown above the table in the default format.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub name: String,
+    pub title: Option<String>,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn new(name: &str, columns: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            title: None,
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn push(&mut self, row: Vec<Value>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+}
+
+
+pub fn render(tables: &[Table], format: Format) -> String {
+    match format {
+        Format::Table => render_text(tables),
+        Format::Json => render_json(tables),
+        Format::Csv => render_delimited(tables, ','),
+        Format::Tsv => render_delimited(tables, '\t'),
+    }
+}
+
+pub fn print(tables: &[Table], format: Format) {
+    let text = render(tables, format);
+
+    // Ignore write errors so piping into `head` and friends doesn't panic
+    // once the reader closes the pipe.
+    if !text.is_empty() {
+        let _ = writeln!(io::stdout().lock(), "{}", text.trim_end());
+    }
+}
+
+
+fn render_text(tables: &[Table]) -> String {
+    let mut out = String::new();
+
+    for table in tables {
+        if let Some(title) = &table.title {
+            out.push_str(title);
+            out.push('\n');
+        }
+
+        let cells: Vec<Vec<String>> = table
+            .rows
+            .iter()
+            .map(|row| row.iter().map(Value::to_plain).collect())
+            .collect();
+
+        let widths: Vec<usize> = table
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, col)| {
+                cells
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain(std::iter::once(col.chars().count()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let header: Vec<String> = table
+            .columns
+            .iter()
+            .zip(&widths)
+            .map(|(col, w)| format!("{:<w$}", col, w = w))
+            .collect();
+        out.push_str(header.join("  ").trim_end());
+        out.push('\n');
+
+        let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        out.push_str(&rule.join("  "));
+        out.push('\n');
+
+        for (row, values) in cells.iter().zip(&table.rows) {
+            let line: Vec<String> = row
+                .iter()
+                .zip(values)
+                .zip(&widths)
+                .map(|((cell, value), w)| {
+                    if value.is_numeric() {
+                        format!("{:>w$}", cell, w = w)
+                    } else {
+                        format!("{:<w$}", cell, w = w)
+                    }
+                })
+                .collect();
+            out.push_str(line.join("  ").trim_end());
+            out.push('\n');
+        }
+
+        out.push('\n');
+    }
+
+    out
+}
+
+
+fn rows_to_json(table: &Table) -> JsonValue {
+    let rows = table
+        .rows
+        .iter()
+        .map(|row| {
+            let object: Map<String, JsonValue> = table
+                .columns
+                .iter()
+                .cloned()
+                .zip(row.iter().map(|v| serde_json::to_value(v).unwrap_or(JsonValue::Null)))
+                .collect();
+            JsonValue::Object(object)
+        })
+        .collect();
+
+    JsonValue::Array(rows)
+}
+
+/// Always an object keyed by table name, however many tables there are, so
+/// adding a section to a command doesn't change the shape of its output. A
+/// repeated name gets a `_2`, `_3`… suffix.
+fn render_json(tables: &[Table]) -> String {
+    let mut object = Map::new();
+    for table in tables {
+        let mut key = table.name.clone();
+        let mut n = 1;
+        while object.contains_key(&key) {
+            n += 1;
+            key = format!("{}_{}", table.name, n);
+        }
+        object.insert(key, rows_to_json(table));
+    }
+
+    serde_json::to_string_pretty(&JsonValue::Object(object)).expect("Failed to serialize output")
+}
+
+
+fn escape_field(field: &str, delimiter: char) -> String {
+    if delimiter == '\t' {
+        return field.replace(['\t', '\n', '\r'], " ");
+    }
+
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn render_delimited(tables: &[Table], delimiter: char) -> String {
+    let sep = delimiter.to_string();
+    let mut out = String::new();
+
+    for (i, table) in tables.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+
+        let header: Vec<String> = table
+            .columns
+            .iter()
+            .map(|c| escape_field(c, delimiter))
+            .collect();
+        out.push_str(&header.join(&sep));
+        out.push('\n');
+
+        for row in &table.rows {
+            let line: Vec<String> = row
+                .iter()
+                .map(|v| match v {
+                    Value::Real(r) => r.to_string(),
+                    other => escape_field(&other.to_plain(), delimiter),
+                })
+                .collect();
+            out.push_str(&line.join(&sep));
+            out.push('\n');
+        }
+    }
+
+    out
+}
@@ -0,0 +1,493 @@
+use std::fs;
+use std::sync::LazyLock;
+use chrono::NaiveDate;
+use regex::Regex;
+use crate::models::account::AccountKind;
+use super::ParsedTransaction;
+
+// Amounts in any other commodity have no exchange rate to convert with, so
+// postings in them are skipped unless the journal gives a price in lei.
+const HOME_COMMODITIES: [&str; 3] = ["RON", "LEI", "LEU"];
+
+/// A quoted beancount string, with `\"` escapes.
+static QUOTED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""((?:[^"\\]|\\.)*)""#).unwrap());
+/// What separates a posting's account from its amount: account names may
+/// contain single spaces, so at least two spaces or a tab.
+static AMOUNT_SEPARATOR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s{2,}|\t").unwrap());
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Dialect {
+    Ledger,
+    Beancount,
+}
+
+struct Posting {
+    account: String,
+    amount: Option<(f64, String)>,
+}
+
+struct Entry {
+    line: usize,
+    date: NaiveDate,
+    description: String,
+    tags: Vec<String>,
+    postings: Vec<Posting>,
+}
+
+
+pub fn parse_ledger(file_path: &str) -> Vec<ParsedTransaction> {
+    parse_journal(file_path, Dialect::Ledger)
+}
+
+pub fn parse_beancount(file_path: &str) -> Vec<ParsedTransaction> {
+    parse_journal(file_path, Dialect::Beancount)
+}
+
+
+fn parse_journal(file_path: &str, dialect: Dialect) -> Vec<ParsedTransaction> {
+    let text = fs::read_to_string(file_path).expect("Failed to open file");
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut in_entry = false;
+
+    for (i, raw) in text.lines().enumerate() {
+        let line = strip_comment(raw);
+
+        if raw.starts_with([' ', '\t']) {
+            if !in_entry {
+                continue;
+            }
+            let entry = entries.last_mut().expect("entry in progress");
+            let trimmed = raw.trim();
+
+            // Comments (on their own line or after a posting) can carry tags.
+            entry.tags.extend(comment_tags(trimmed));
+            if trimmed.starts_with([';', '#']) || is_metadata(trimmed, dialect) {
+                continue;
+            }
+
+            if let Some(posting) = parse_posting(line.trim()) {
+                entry.postings.push(posting);
+            }
+            continue;
+        }
+
+        in_entry = false;
+        if let Some(mut entry) = parse_header(i + 1, line.trim_end(), dialect) {
+            entry.tags.extend(comment_tags(raw));
+            entries.push(entry);
+            in_entry = true;
+        }
+    }
+
+    let mut transactions = Vec::new();
+    for entry in entries {
+        transactions.extend(to_parsed(entry));
+    }
+    transactions
+}
+
+
+fn strip_comment(line: &str) -> &str {
+    line.split(';').next().unwrap_or("")
+}
+
+fn is_metadata(line: &str, dialect: Dialect) -> bool {
+    // Beancount metadata looks like `key: value` with a lowercase key,
+    // which can't be confused with a capitalised account name.
+    dialect == Dialect::Beancount
+        && line.starts_with(|c: char| c.is_ascii_lowercase())
+        && line.split_once(':').is_some_and(|(key, _)| !key.contains(char::is_whitespace))
+}
+
+/// Collects ledger-style `:tag1:tag2:` and `tag: value` comment tags.
+fn comment_tags(line: &str) -> Vec<String> {
+    let Some(pos) = line.find(';') else {
+        return Vec::new();
+    };
+    let comment = line[pos + 1..].trim();
+    let mut tags = Vec::new();
+
+    for part in comment.split(',') {
+        let part = part.trim();
+        if part.starts_with(':') && part.ends_with(':') && part.len() > 2 {
+            tags.extend(part.split(':').filter(|t| !t.is_empty()).map(str::to_string));
+        } else if let Some((name, _)) = part.split_once(':')
+            && !name.is_empty()
+            && !name.contains(char::is_whitespace)
+            // `id:` is the row id written by `export`, not a user tag.
+            && name != "id"
+        {
+            tags.push(name.to_string());
+        }
+    }
+
+    tags
+}
+
+
+fn parse_journal_date(token: &str) -> Option<NaiveDate> {
+    // Ledger allows a secondary date after `=`; the primary one is enough here.
+    let token = token.split('=').next()?;
+    ["%Y-%m-%d", "%Y/%m/%d", "%Y.%m.%d"]
+        .iter()
+        .find_map(|fmt| NaiveDate::parse_from_str(token, fmt).ok())
+}
+
+fn parse_header(line_number: usize, line: &str, dialect: Dialect) -> Option<Entry> {
+    let (date_token, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let date = parse_journal_date(date_token)?;
+    let rest = rest.trim();
+
+    let (description, tags) = match dialect {
+        Dialect::Ledger => {
+            let rest = rest.trim_start_matches(['*', '!']).trim_start();
+            // Drop an optional `(code)` before the description.
+            let rest = match rest.strip_prefix('(').and_then(|r| r.split_once(')')) {
+                Some((_, after)) => after.trim(),
+                None => rest,
+            };
+            (rest.to_string(), Vec::new())
+        }
+        Dialect::Beancount => {
+            // Only transactions are imported; `open`, `balance`, `price` and
+            // the other directives are skipped.
+            let (flag, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if !matches!(flag, "*" | "!" | "txn") {
+                return None;
+            }
+
+            let strings: Vec<&str> = QUOTED
+                .captures_iter(rest)
+                .map(|c| c.get(1).map_or("", |m| m.as_str()))
+                .collect();
+            let description = match strings.as_slice() {
+                [payee, narration] if !payee.is_empty() && !narration.is_empty() => {
+                    format!("{} - {}", payee, narration)
+                }
+                [payee, narration] => format!("{}{}", payee, narration),
+                [narration] => narration.to_string(),
+                _ => String::new(),
+            };
+
+            let tags = QUOTED
+                .replace_all(rest, "")
+                .split_whitespace()
+                .filter_map(|w| w.strip_prefix('#'))
+                .map(str::to_string)
+                .collect();
+            (description, tags)
+        }
+    };
+
+    Some(Entry { line: line_number, date, description, tags, postings: Vec::new() })
+}
+
+fn parse_posting(line: &str) -> Option<Posting> {
+    if line.is_empty() {
+        return None;
+    }
+
+    // Beancount postings may start with a flag.
+    let line = line.strip_prefix(['*', '!']).map(str::trim_start).unwrap_or(line);
+
+    let (account, amount) = match AMOUNT_SEPARATOR.find(line) {
+        Some(m) => (&line[..m.start()], line[m.end()..].trim()),
+        None => (line, ""),
+    };
+
+    // Virtual postings don't have to balance and don't move money.
+    if account.starts_with(['(', '[']) {
+        return None;
+    }
+
+    Some(Posting {
+        account: account.trim().to_string(),
+        amount: parse_amount(amount),
+    })
+}
+
+
+fn split_number(text: &str) -> Option<(f64, String)> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    let negative = text.contains('-');
+    let digits: String = text
+        .chars()
+        .filter(|c| c.is_ascii_digit() || matches!(c, '.' | ','))
+        .collect();
+    let commodity: String = text
+        .chars()
+        .filter(|c| !(c.is_ascii_digit() || matches!(c, '.' | ',' | '-' | '+' | '"') || c.is_whitespace()))
+        .collect();
+
+    let value: f64 = decimal_number(&digits)?.parse().ok()?;
+    Some((if negative { -value } else { value }, commodity))
+}
+
+/// Normalises digits written with `.` or `,` as the decimal mark. With both,
+/// the last one is the decimal mark (`1.200,50`, `1,200.50`); a separator
+/// that appears only once is the decimal mark (`12,50`), and one repeated is
+/// a thousands separator (`1,000,000`).
+fn decimal_number(digits: &str) -> Option<String> {
+    let mark = match digits.rfind(['.', ',']) {
+        Some(at) => {
+            let mark = digits[at..].chars().next()?;
+            let other = if mark == '.' { ',' } else { '.' };
+            (digits.contains(other) || digits.matches(mark).count() == 1).then_some(mark)
+        }
+        None => None,
+    };
+
+    Some(
+        digits
+            .chars()
+            .filter_map(|c| match c {
+                '0'..='9' => Some(c),
+                c if Some(c) == mark => Some('.'),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
+/// Parses a posting amount, converting it to lei when a `@`/`@@` price or a
+/// `{cost}`/`{{total cost}}` in lei is given. A price wins over the cost.
+fn parse_amount(text: &str) -> Option<(f64, String)> {
+    // Balance assertions (`= 100 RON`) don't change the posting amount.
+    let text = text.split('=').next().unwrap_or("").trim();
+
+    // Take the lot annotation out first, so `{900 RON} @ 950 RON` isn't read
+    // as part of the quantity.
+    let (text, cost) = match (text.find('{'), text.rfind('}')) {
+        (Some(open), Some(close)) if open < close => {
+            let lot = &text[open..=close];
+            let cost = match lot.strip_prefix("{{").and_then(|l| l.strip_suffix("}}")) {
+                Some(total) => (total, true),
+                None => (&lot[1..lot.len() - 1], false),
+            };
+            (format!("{} {}", &text[..open], &text[close + 1..]), Some(cost))
+        }
+        _ => (text.to_string(), None),
+    };
+
+    let (amount, price) = if let Some((a, p)) = text.split_once("@@") {
+        (a, Some((p, true)))
+    } else if let Some((a, p)) = text.split_once('@') {
+        (a, Some((p, false)))
+    } else {
+        // Beancount costs can also carry a date or label after `, `.
+        (text.as_str(), cost.map(|(c, total)| (c.split(", ").next().unwrap_or(c), total)))
+    };
+
+    let (quantity, commodity) = split_number(amount)?;
+
+    if let Some((price, total_price)) = price
+        && !is_home(&commodity)
+        && let Some((price, price_commodity)) = split_number(price)
+    {
+        let value = if total_price { price.abs() * quantity.signum() } else { price * quantity };
+        return Some((value, price_commodity));
+    }
+
+    Some((quantity, commodity))
+}
+
+fn is_home(commodity: &str) -> bool {
+    commodity.is_empty() || HOME_COMMODITIES.iter().any(|c| c.eq_ignore_ascii_case(commodity))
+}
+
+
+/// Returns the category for an expense or income account, e.g.
+/// `Expenses:Food:Groceries` → `Food:Groceries`.
+fn account_category(account: &str) -> Option<String> {
+    let (root, rest) = account.split_once(':').unwrap_or((account, ""));
+    let root = root.to_lowercase();
+    let is_category = matches!(root.as_str(), "expenses" | "expense" | "income" | "revenue" | "revenues");
+    if !is_category {
+        return None;
+    }
+    Some(if rest.is_empty() { "Uncategorized".to_string() } else { rest.to_string() })
+}
+
+/// Returns the account name and kind for an asset or liability account,
+/// e.g. `Assets:Bank:Checking` → `Bank:Checking`.
+fn balance_account(account: &str) -> Option<(String, AccountKind)> {
+    let (root, rest) = account.split_once(':').unwrap_or((account, ""));
+    let kind = match root.to_lowercase().as_str() {
+        "assets" | "asset" => AccountKind::Asset,
+        "liabilities" | "liability" => AccountKind::Liability,
+        _ => return None,
+    };
+    Some((if rest.is_empty() { root.to_string() } else { rest.to_string() }, kind))
+}
+
+fn to_parsed(mut entry: Entry) -> Vec<ParsedTransaction> {
+    // At most one posting may leave its amount out; it takes whatever
+    // balances the rest, once for each commodity.
+    let mut known: Vec<(String, f64)> = Vec::new();
+    for (value, commodity) in entry.postings.iter().filter_map(|p| p.amount.as_ref()) {
+        match known.iter_mut().find(|(c, _)| c == commodity) {
+            Some((_, sum)) => *sum += value,
+            None => known.push((commodity.clone(), *value)),
+        }
+    }
+    if known.is_empty() {
+        known.push((String::new(), 0.0));
+    }
+    let postings = std::mem::take(&mut entry.postings);
+    for posting in postings {
+        if posting.amount.is_some() {
+            entry.postings.push(posting);
+            continue;
+        }
+        for (commodity, sum) in &known {
+            entry.postings.push(Posting { account: posting.account.clone(), amount: Some((-sum, commodity.clone())) });
+        }
+    }
+
+    entry.tags.sort();
+    entry.tags.dedup();
+    let mut description = entry.description.trim().to_string();
+    for tag in &entry.tags {
+        description.push_str(&format!(" #{}", tag));
+    }
+
+    // The asset or liability side of the entry says which account the money
+    // moved through.
+    let (account, account_kind) = entry
+        .postings
+        .iter()
+        .find_map(|p| balance_account(&p.account))
+        .map_or((None, AccountKind::Asset), |(name, kind)| (Some(name), kind));
+
+    let mut parsed = Vec::new();
+    for posting in entry.postings {
+        let Some(category) = account_category(&posting.account) else {
+            continue;
+        };
+        let Some((value, commodity)) = posting.amount else {
+            continue;
+        };
+        if !is_home(&commodity) {
+            eprintln!(
+                "Skipping line {}: {} {} in {} has no price in RON.",
+                entry.line, value, commodity, posting.account
+            );
+            continue;
+        }
+
+        // Journals post expenses as positive amounts; here they are negative.
+        parsed.push(ParsedTransaction {
+            amount: -value,
+            category,
+            description: description.trim().to_string(),
+            date: entry.date.format("%m/%d/%Y").to_string(),
+            account: account.clone(),
+            account_kind,
+        });
+    }
+
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(postings: &[&str]) -> Entry {
+        Entry {
+            line: 1,
+            date: NaiveDate::from_ymd_opt(2024, 3, 5).unwrap(),
+            description: "Mega Image".to_string(),
+            tags: vec!["food".to_string()],
+            postings: postings.iter().filter_map(|p| parse_posting(p)).collect(),
+        }
+    }
+
+    #[test]
+    fn splits_account_from_amount() {
+        let posting = parse_posting("Expenses:Eating Out  42.50 RON").unwrap();
+        assert_eq!(posting.account, "Expenses:Eating Out");
+        assert_eq!(posting.amount, Some((42.5, "RON".to_string())));
+
+        let posting = parse_posting("! Assets:Bank\t-1,200.00 RON = 300 RON").unwrap();
+        assert_eq!(posting.account, "Assets:Bank");
+        assert_eq!(posting.amount, Some((-1200.0, "RON".to_string())));
+
+        assert!(parse_posting("Assets:Cash").unwrap().amount.is_none());
+        assert!(parse_posting("[Budget:Food]  -10 RON").is_none());
+    }
+
+    #[test]
+    fn converts_priced_amounts() {
+        assert_eq!(parse_amount("10 EUR @ 5 RON"), Some((50.0, "RON".to_string())));
+        assert_eq!(parse_amount("-10 EUR @@ 49.70 RON"), Some((-49.7, "RON".to_string())));
+        assert_eq!(parse_amount("2 AAPL {900 RON}"), Some((1800.0, "RON".to_string())));
+        assert_eq!(parse_amount("10 EUR"), Some((10.0, "EUR".to_string())));
+    }
+
+    #[test]
+    fn reads_either_decimal_mark() {
+        assert_eq!(parse_amount("12,50 RON"), Some((12.5, "RON".to_string())));
+        assert_eq!(parse_amount("-12.50 RON"), Some((-12.5, "RON".to_string())));
+        assert_eq!(parse_amount("1.200,50 RON"), Some((1200.5, "RON".to_string())));
+        assert_eq!(parse_amount("1,200.50 RON"), Some((1200.5, "RON".to_string())));
+        assert_eq!(parse_amount("1,000,000 RON"), Some((1_000_000.0, "RON".to_string())));
+        assert_eq!(parse_amount("RON 7"), Some((7.0, "RON".to_string())));
+    }
+
+    #[test]
+    fn lot_costs_are_not_part_of_the_quantity() {
+        assert_eq!(parse_amount("2 AAPL {900 RON} @ 950 RON"), Some((1900.0, "RON".to_string())));
+        assert_eq!(parse_amount("2 AAPL {{1800 RON}}"), Some((1800.0, "RON".to_string())));
+        assert_eq!(parse_amount("-2 AAPL {{1800 RON}} @@ 1900 RON"), Some((-1900.0, "RON".to_string())));
+        assert_eq!(parse_amount("2 AAPL {900 RON, 2024-01-05}"), Some((1800.0, "RON".to_string())));
+    }
+
+    #[test]
+    fn the_elided_amount_balances_each_commodity() {
+        let parsed = to_parsed(entry(&["Assets:Bank  -50 RON", "Assets:Wallet  -10 EUR", "Expenses:Travel"]));
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].amount, -50.0);
+    }
+
+    #[test]
+    fn the_elided_amount_balances_the_rest() {
+        let parsed = to_parsed(entry(&["Expenses:Food  30 RON", "Expenses:Home  20 RON", "Assets:Bank:Checking"]));
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].amount, -30.0);
+        assert_eq!(parsed[1].amount, -20.0);
+        assert_eq!(parsed[0].description, "Mega Image #food");
+        assert_eq!(parsed[0].date, "03/05/2024");
+        assert_eq!(parsed[0].account.as_deref(), Some("Bank:Checking"));
+        assert_eq!(parsed[0].account_kind, AccountKind::Asset);
+    }
+
+    #[test]
+    fn income_is_positive() {
+        let parsed = to_parsed(entry(&["Assets:Bank  500 RON", "Income:Salary"]));
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].category, "Salary");
+        assert_eq!(parsed[0].amount, 500.0);
+    }
+
+    #[test]
+    fn liabilities_are_accounts_too() {
+        let parsed = to_parsed(entry(&["Expenses:Food  15 RON", "Liabilities:Card"]));
+        assert_eq!(parsed[0].amount, -15.0);
+        assert_eq!(parsed[0].account.as_deref(), Some("Card"));
+        assert_eq!(parsed[0].account_kind, AccountKind::Liability);
+    }
+
+    #[test]
+    fn names_balance_accounts() {
+        assert_eq!(balance_account("Assets:Bank:Checking"), Some(("Bank:Checking".to_string(), AccountKind::Asset)));
+        assert_eq!(balance_account("liabilities:Card"), Some(("Card".to_string(), AccountKind::Liability)));
+        assert_eq!(balance_account("Assets"), Some(("Assets".to_string(), AccountKind::Asset)));
+        assert_eq!(balance_account("Expenses:Food"), None);
+        assert_eq!(account_category("Expenses"), Some("Uncategorized".to_string()));
+    }
+}
@@ -1,34 +1,57 @@
+mod journal;
+
+pub use journal::{parse_beancount, parse_ledger};
+
 use std::fs::File;
-use std::io::{BufReader, BufRead};
+use chrono::NaiveDate;
+use crate::models::account::AccountKind;
 
 pub struct ParsedTransaction {
     pub amount: f64,
     pub category: String,
 	pub description: String,
     pub date: String,
+    /// The account named in the file, if any
+    pub account: Option<String>,
+    /// Whether `account` holds money or debt, for creating it
+    pub account_kind: AccountKind,
 }
 
+/// Reads `amount,category,description,date[,account]` rows after a header
+/// line. Fields may be quoted, so descriptions can contain commas.
 pub fn parse_csv(file_path: &str) -> Vec<ParsedTransaction> {
     let file = File::open(file_path).expect("Failed to open file");
-    let reader = BufReader::new(file);
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(file);
 
-    reader.lines()
-        .skip(1)
-        .filter_map(|line| {
-            if let Ok(entry) = line {
-                let parts: Vec<&str> = entry.split(',').collect();
-                if parts.len() >= 4 {
-                    let amount = parts[0].trim().parse().ok()?;
-                    let category = parts[1].trim().to_string();
-					let description = parts[2].trim().to_string();
-                    let date = parts[3].trim().to_string();
-                    Some(ParsedTransaction { amount, category, description, date })
-                } else {
-                    None
-                }
-            } else {
-                None
+    reader.records()
+        .filter_map(|record| {
+            let record = record.ok()?;
+            if record.len() < 4 {
+                return None;
             }
+            let amount = record[0].parse().ok()?;
+            let account = record.get(4).filter(|a| !a.is_empty()).map(str::to_string);
+            Some(ParsedTransaction {
+                amount,
+                category: record[1].to_string(),
+                description: record[2].to_string(),
+                date: record[3].to_string(),
+                account,
+                account_kind: AccountKind::Asset,
+            })
         })
         .collect()
 }
+
+
+/// Parses a stored transaction date. CSV imports use `MM/DD/YYYY`, while
+/// `add` without `--date` stores `YYYY-MM-DD`, so both are accepted.
+pub fn parse_date(date: &str) -> Option<NaiveDate> {
+    let date = date.trim();
+    NaiveDate::parse_from_str(date, "%m/%d/%Y")
+        .or_else(|_| NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+        .ok()
+}
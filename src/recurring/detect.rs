@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+use chrono::{Days, Months, NaiveDate};
+use rusqlite::Connection;
+use crate::db::ISO_DATE;
+use crate::stats::median;
+
+/// How often a recurring transaction repeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cadence {
+    Weekly,
+    Biweekly,
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl Cadence {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Cadence::Weekly => "weekly",
+            Cadence::Biweekly => "biweekly",
+            Cadence::Monthly => "monthly",
+            Cadence::Quarterly => "quarterly",
+            Cadence::Yearly => "yearly",
+        }
+    }
+
+    /// The cadence whose usual gap (with some slack for weekends and
+    /// shorter months) contains `days`.
+    fn from_gap(days: i64) -> Option<Self> {
+        match days {
+            5..=9 => Some(Cadence::Weekly),
+            12..=16 => Some(Cadence::Biweekly),
+            26..=35 => Some(Cadence::Monthly),
+            80..=100 => Some(Cadence::Quarterly),
+            350..=380 => Some(Cadence::Yearly),
+            _ => None,
+        }
+    }
+
+    /// How many days late or early a charge may be and still count as on time.
+    fn slack(&self) -> i64 {
+        match self {
+            Cadence::Weekly | Cadence::Biweekly => 2,
+            Cadence::Monthly => 4,
+            Cadence::Quarterly => 7,
+            Cadence::Yearly => 10,
+        }
+    }
+
+    pub fn per_year(&self) -> f64 {
+        match self {
+            Cadence::Weekly => 52.0,
+            Cadence::Biweekly => 26.0,
+            Cadence::Monthly => 12.0,
+            Cadence::Quarterly => 4.0,
+            Cadence::Yearly => 1.0,
+        }
+    }
+
+    /// How many steps of this cadence lead from `from` to `to`, allowing a
+    /// few skipped occurrences in between.
+    fn steps(&self, from: NaiveDate, to: NaiveDate) -> Option<u32> {
+        (1..=MAX_STEPS).find(|n| (to - self.after(from, *n)).num_days().abs() <= self.slack())
+    }
+
+    /// The `n`th occurrence after `date`. Monthly steps keep the day of the
+    /// month where possible (the 31st falls on the last day of shorter months).
+    pub fn after(&self, date: NaiveDate, n: u32) -> NaiveDate {
+        match self {
+            Cadence::Weekly => date + Days::new(7 * n as u64),
+            Cadence::Biweekly => date + Days::new(14 * n as u64),
+            Cadence::Monthly => date + Months::new(n),
+            Cadence::Quarterly => date + Months::new(3 * n),
+            Cadence::Yearly => date + Months::new(12 * n),
+        }
+    }
+}
+
+/// Transactions with the same description, category and account that repeat
+/// at a regular interval for a similar amount.
+#[derive(Debug, Clone)]
+pub struct Recurring {
+    pub description: String,
+    pub category: Option<String>,
+    pub account: Option<String>,
+    pub cadence: Cadence,
+    /// Dates it was seen on, oldest first
+    pub dates: Vec<NaiveDate>,
+    /// The amount on each of `dates`
+    pub amounts: Vec<f64>,
+    /// Dates on which it was charged more than once, with the number of charges
+    pub duplicates: Vec<(NaiveDate, usize)>,
+    /// Expected dates between the first and last one on which it didn't occur
+    pub missed: Vec<NaiveDate>,
+}
+
+impl Recurring {
+    pub fn last_date(&self) -> NaiveDate {
+        *self.dates.last().unwrap()
+    }
+
+    /// The most recent amount, used for projections.
+    pub fn amount(&self) -> f64 {
+        *self.amounts.last().unwrap()
+    }
+
+    /// The median amount.
+    pub fn typical(&self) -> f64 {
+        median(&self.amounts)
+    }
+
+    /// The latest rise in price that still holds: the date it started, the
+    /// typical amount before it and the current amount. A one-off spike, or a
+    /// rise that has since gone back down, isn't reported.
+    pub fn price_increase(&self) -> Option<(NaiveDate, f64, f64)> {
+        let current = self.amount().abs();
+        (1..self.amounts.len())
+            .rev()
+            .filter(|i| self.amounts[*i].abs() > self.amounts[i - 1].abs() + 0.005)
+            // A charge well above today's price was an outlier, not the rise.
+            .filter(|i| self.amounts[*i].abs() <= current * (1.0 + AMOUNT_TOLERANCE))
+            .map(|i| (i, median(&self.amounts[..i])))
+            .find(|(_, before)| current > before.abs() + 0.005)
+            .map(|(i, before)| (self.dates[i], before, self.amount()))
+    }
+
+    /// The date after the last one it's expected on.
+    pub fn next_expected(&self) -> NaiveDate {
+        self.cadence.after(self.last_date(), 1)
+    }
+
+    /// True when the next expected date has passed by more than the usual slack.
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        (today - self.next_expected()).num_days() > self.cadence.slack()
+    }
+
+    /// Expected dates after the last one, up to and including `to`.
+    pub fn upcoming(&self, to: NaiveDate) -> Vec<NaiveDate> {
+        (1..)
+            .map(|n| self.cadence.after(self.last_date(), n))
+            .take_while(|d| *d <= to)
+            .collect()
+    }
+
+    /// True when at least two expected occurrences have gone by without it.
+    pub fn is_stale(&self, today: NaiveDate) -> bool {
+        self.cadence.after(self.last_date(), 2) < today
+    }
+}
+
+const MIN_OCCURRENCES: usize = 3;
+/// Gaps longer than this many occurrences split a series rather than count as missed.
+const MAX_STEPS: u32 = 3;
+/// How far an amount may stray from the median and still count as the same charge.
+const AMOUNT_TOLERANCE: f64 = 0.25;
+
+/// Groups transactions by payee and keeps the groups that repeat regularly:
+/// at least three dates, most gaps matching one cadence (possibly with a
+/// skipped occurrence) and most amounts close to the typical one. Several
+/// transactions on the same day count once and are reported as duplicates.
+pub fn detect(conn: &Connection) -> Vec<Recurring> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT TRIM(description), category, account, {date} AS day, amount
+         FROM transactions
+         WHERE description IS NOT NULL AND TRIM(description) <> ''
+         ORDER BY day, id",
+        date = ISO_DATE
+    )).unwrap();
+
+    // Payees are matched case-insensitively and shown as last written.
+    type Key = (String, Option<String>, Option<String>);
+    // (date, amount, number of charges that day)
+    type Charges = Vec<(NaiveDate, f64, usize)>;
+    let mut groups: BTreeMap<Key, (String, Charges)> = BTreeMap::new();
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            (row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, Option<String>>(2)?),
+            row.get::<_, String>(3)?,
+            row.get::<_, f64>(4)?,
+        ))
+    }).unwrap();
+    for ((description, category, account), day, amount) in rows.flatten() {
+        let Ok(date) = NaiveDate::parse_from_str(&day, "%Y-%m-%d") else { continue };
+        let (name, seen) = groups.entry((description.to_lowercase(), category, account)).or_default();
+        *name = description;
+        match seen.last_mut() {
+            Some((d, _, count)) if *d == date => *count += 1,
+            _ => seen.push((date, amount, 1)),
+        }
+    }
+
+    let mut found = Vec::new();
+    for ((_, category, account), (description, seen)) in groups {
+        if seen.len() < MIN_OCCURRENCES {
+            continue;
+        }
+        let gaps: Vec<f64> = seen.windows(2).map(|w| (w[1].0 - w[0].0).num_days() as f64).collect();
+        let Some(cadence) = Cadence::from_gap(median(&gaps).round() as i64) else {
+            continue;
+        };
+        let steps: Vec<Option<u32>> = seen.windows(2).map(|w| cadence.steps(w[0].0, w[1].0)).collect();
+        let regular = steps.iter().filter(|s| s.is_some()).count();
+        if regular * 3 < steps.len() * 2 {
+            continue;
+        }
+
+        let typical = median(&seen.iter().map(|(_, a, _)| *a).collect::<Vec<_>>());
+        let similar = seen.iter().filter(|(_, a, _)| (a - typical).abs() <= typical.abs() * AMOUNT_TOLERANCE).count();
+        if similar * 3 < seen.len() * 2 {
+            continue;
+        }
+
+        let missed = seen
+            .windows(2)
+            .zip(&steps)
+            .flat_map(|(w, n)| (1..n.unwrap_or(1)).map(move |k| cadence.after(w[0].0, k)))
+            .collect();
+
+        found.push(Recurring {
+            description,
+            category,
+            account,
+            cadence,
+            dates: seen.iter().map(|(d, _, _)| *d).collect(),
+            amounts: seen.iter().map(|(_, a, _)| *a).collect(),
+            duplicates: seen.iter().filter(|(_, _, n)| *n > 1).map(|(d, _, n)| (*d, *n)).collect(),
+            missed,
+        });
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monthly(amounts: &[f64]) -> Recurring {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        Recurring {
+            description: "Netflix".to_string(),
+            category: None,
+            account: None,
+            cadence: Cadence::Monthly,
+            dates: (0..amounts.len() as u32).map(|i| start + Months::new(i)).collect(),
+            amounts: amounts.to_vec(),
+            duplicates: Vec::new(),
+            missed: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reports_a_rise_that_holds() {
+        let r = monthly(&[-40.0, -40.0, -40.0, -45.0, -45.0]);
+        let (date, before, now) = r.price_increase().unwrap();
+        assert_eq!(date, NaiveDate::from_ymd_opt(2024, 4, 15).unwrap());
+        assert_eq!((before, now), (-40.0, -45.0));
+    }
+
+    #[test]
+    fn ignores_a_rise_that_went_back_down() {
+        assert!(monthly(&[-40.0, -45.0, -40.0, -40.0]).price_increase().is_none());
+    }
+
+    #[test]
+    fn skips_a_one_off_spike_for_the_rise_before_it() {
+        let r = monthly(&[-40.0, -40.0, -45.0, -90.0, -45.0]);
+        let (date, before, now) = r.price_increase().unwrap();
+        assert_eq!(date, NaiveDate::from_ymd_opt(2024, 3, 15).unwrap());
+        assert_eq!((before, now), (-40.0, -45.0));
+    }
+
+    #[test]
+    fn steady_price_has_no_increase() {
+        assert!(monthly(&[-40.0, -40.0, -40.0]).price_increase().is_none());
+    }
+}
@@ -0,0 +1,285 @@
+pub mod detect;
+pub mod subscriptions;
+
+use chrono::{Days, NaiveDate};
+use rusqlite::Connection;
+use crate::db::{self, ISO_DATE};
+use crate::models::recurring::RecurringRule;
+use crate::output::Table;
+
+/// Imported transactions this many days either side of a due date can stand
+/// in for it, since banks book on business days.
+const MATCH_DAYS: u64 = 3;
+
+pub fn add_rule(conn: &Connection, rule: &RecurringRule) -> i64 {
+    conn.execute(
+        "INSERT INTO recurring_rules (amount, category, description, account, rule, start_date, end_date, auto_post)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        (
+            rule.amount,
+            &rule.category,
+            &rule.description,
+            &rule.account,
+            &rule.rule,
+            rule.start_date.to_string(),
+            rule.end_date.map(|d| d.to_string()),
+            rule.auto_post,
+        ),
+    ).expect("Failed to save recurring transaction");
+    conn.last_insert_rowid()
+}
+
+/// Deletes a rule. Transactions it already posted are kept.
+pub fn remove_rule(conn: &Connection, id: i64) -> bool {
+    conn.execute("DELETE FROM recurring_posts WHERE rule_id = ?1", [id]).expect("Failed to delete recurring transaction");
+    conn.execute("DELETE FROM recurring_rules WHERE id = ?1", [id]).expect("Failed to delete recurring transaction") > 0
+}
+
+/// The transaction with id above `after_id` that best fits a payment of
+/// `amount` due on `date`: the same amount, booked within a few days of it
+/// and not already claimed by a rule. Imported rows often lack an account or
+/// category, so a missing one is accepted, but one that differs isn't. Rows
+/// that have both are preferred, then the closest date.
+fn find_match(
+    conn: &Connection,
+    after_id: i64,
+    amount: f64,
+    date: NaiveDate,
+    account: Option<&str>,
+    category: Option<&str>,
+) -> Option<i64> {
+    conn.query_row(
+        &format!(
+            "SELECT id FROM transactions
+             WHERE id > ?1 AND ABS(amount - ?2) < 0.005
+             AND {date} BETWEEN ?3 AND ?4
+             AND id NOT IN (SELECT transaction_id FROM recurring_posts)
+             AND (?5 IS NULL OR NULLIF(account, '') IS NULL OR account = ?5)
+             AND (?6 IS NULL OR NULLIF(category, '') IS NULL OR category = ?6)
+             ORDER BY account IS ?5 DESC, category IS ?6 DESC, ABS(julianday({date}) - julianday(?7)), id
+             LIMIT 1",
+            date = ISO_DATE
+        ),
+        (
+            after_id,
+            amount,
+            (date - Days::new(MATCH_DAYS)).to_string(),
+            (date + Days::new(MATCH_DAYS)).to_string(),
+            account,
+            category,
+            date.to_string(),
+        ),
+        |row| row.get(0),
+    ).ok()
+}
+
+/// Swaps instances that were posted from a schedule for the transactions
+/// imported since `after_id` that turn out to be the same payment, deleting
+/// the posted copy. Returns how many were replaced.
+pub fn replace_posted(conn: &Connection, after_id: i64) -> usize {
+    let mut stmt = conn.prepare(
+        "SELECT p.rule_id, p.date, p.transaction_id, t.amount, t.category, t.account
+         FROM recurring_posts p JOIN transactions t ON t.id = p.transaction_id
+         WHERE NOT p.matched
+         ORDER BY p.date",
+    ).unwrap();
+    type Posted = (i64, String, i64, f64, Option<String>, Option<String>);
+    let posted: Vec<Posted> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)))
+        .unwrap()
+        .flatten()
+        .collect();
+
+    let mut replaced = 0;
+    for (rule_id, day, posted_id, amount, category, account) in posted {
+        let Ok(date) = NaiveDate::parse_from_str(&day, "%Y-%m-%d") else { continue };
+        let Some(imported) = find_match(conn, after_id, amount, date, account.as_deref(), category.as_deref()) else {
+            continue;
+        };
+        conn.execute(
+            "UPDATE recurring_posts SET transaction_id = ?1, matched = 1 WHERE rule_id = ?2 AND date = ?3",
+            (imported, rule_id, &day),
+        ).expect("Failed to record recurring transaction");
+        conn.execute("DELETE FROM transactions WHERE id = ?1", [posted_id]).expect("Failed to delete posted transaction");
+        replaced += 1;
+    }
+    replaced
+}
+
+fn is_handled(conn: &Connection, rule: &RecurringRule, date: NaiveDate) -> bool {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM recurring_posts WHERE rule_id = ?1 AND date = ?2)",
+        (rule.id, date.to_string()),
+        |row| row.get(0),
+    ).unwrap()
+}
+
+/// One due instance and what was done about it.
+pub struct Posting<'a> {
+    pub rule: &'a RecurringRule,
+    pub date: NaiveDate,
+    /// The posted or matched transaction; `None` on a dry run
+    pub transaction_id: Option<i64>,
+    pub matched: bool,
+}
+
+/// Posts every instance of `rules` due up to `today` that hasn't been posted
+/// yet. Instances that were already imported are linked to the imported
+/// transaction instead of being posted again. With `dry_run`, nothing is
+/// written.
+pub fn post_due<'a>(conn: &Connection, rules: &'a [RecurringRule], today: NaiveDate, dry_run: bool) -> Vec<Posting<'a>> {
+    let mut postings = Vec::new();
+    for rule in rules {
+        for date in rule.due_dates(today) {
+            if is_handled(conn, rule, date) {
+                continue;
+            }
+            let existing = find_match(conn, 0, rule.amount, date, rule.account.as_deref(), Some(&rule.category));
+            let transaction_id = match (existing, dry_run) {
+                (Some(id), _) => Some(id),
+                (None, true) => None,
+                (None, false) => Some(db::add_transaction(
+                    conn,
+                    rule.amount,
+                    &rule.category,
+                    &rule.description,
+                    Some(date.to_string()),
+                    rule.account.as_deref(),
+                )),
+            };
+            if let (Some(id), false) = (transaction_id, dry_run) {
+                conn.execute(
+                    "INSERT INTO recurring_posts (rule_id, date, transaction_id, matched) VALUES (?1, ?2, ?3, ?4)",
+                    (rule.id, date.to_string(), id, existing.is_some()),
+                ).expect("Failed to record recurring transaction");
+            }
+            postings.push(Posting { rule, date, transaction_id, matched: existing.is_some() });
+        }
+    }
+    postings
+}
+
+pub fn postings_table(postings: &[Posting], dry_run: bool) -> Table {
+    let title = match (postings.len(), dry_run) {
+        (0, _) => "Nothing is due.".to_string(),
+        (n, true) => format!("{} instance(s) due (dry run, nothing posted):", n),
+        (n, false) => format!("{} instance(s) due:", n),
+    };
+    let mut table = Table::new("posted", &["rule", "date", "amount", "category", "description", "account", "transaction", "action"])
+        .with_title(title);
+    for p in postings {
+        let action = match (p.matched, dry_run) {
+            (true, _) => "matched existing",
+            (false, true) => "would post",
+            (false, false) => "posted",
+        };
+        table.push(vec![
+            p.rule.id.into(),
+            p.date.to_string().into(),
+            p.rule.amount.into(),
+            p.rule.category.as_str().into(),
+            p.rule.description.as_str().into(),
+            p.rule.account.clone().into(),
+            p.transaction_id.into(),
+            action.into(),
+        ]);
+    }
+    table
+}
+
+pub fn rules_table(conn: &Connection, rules: &[RecurringRule], today: NaiveDate) -> Table {
+    let mut table = Table::new(
+        "recurring",
+        &["id", "amount", "category", "description", "account", "rule", "start", "end", "auto", "posted", "next_due"],
+    )
+    .with_title("Recurring transactions");
+    for rule in rules {
+        let posted: i64 = conn
+            .query_row("SELECT COUNT(*) FROM recurring_posts WHERE rule_id = ?1", [rule.id], |row| row.get(0))
+            .unwrap();
+        let next = rule
+            .due_dates(today + Days::new(400))
+            .into_iter()
+            .find(|d| *d > today || !is_handled(conn, rule, *d));
+        table.push(vec![
+            rule.id.into(),
+            rule.amount.into(),
+            rule.category.as_str().into(),
+            rule.description.as_str().into(),
+            rule.account.clone().into(),
+            rule.rule.as_str().into(),
+            rule.start_date.to_string().into(),
+            rule.end_date.map(|d| d.to_string()).into(),
+            if rule.auto_post { "yes" } else { "no" }.into(),
+            posted.into(),
+            next.map(|d| d.to_string()).into(),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::recurring::Schedule;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn rent(conn: &Connection) -> Vec<RecurringRule> {
+        let start = date(2024, 3, 1);
+        add_rule(conn, &RecurringRule {
+            id: 0,
+            amount: -100.0,
+            category: "Rent".to_string(),
+            description: "Rent".to_string(),
+            account: None,
+            rule: "monthly:1".to_string(),
+            schedule: Schedule::parse("monthly:1", start).unwrap(),
+            start_date: start,
+            end_date: None,
+            auto_post: true,
+        });
+        db::load_rules(conn).unwrap()
+    }
+
+    #[test]
+    fn matches_an_imported_payment_without_a_category() {
+        let conn = db::test_db();
+        let rules = rent(&conn);
+        let imported = db::add_transaction(&conn, -100.0, "", "LANDLORD SRL", Some("2024-03-02".to_string()), None);
+        let postings = post_due(&conn, &rules, date(2024, 3, 5), false);
+        assert_eq!(postings.len(), 1);
+        assert!(postings[0].matched);
+        assert_eq!(postings[0].transaction_id, Some(imported));
+    }
+
+    #[test]
+    fn same_amount_in_another_category_is_not_a_match() {
+        let conn = db::test_db();
+        let rules = rent(&conn);
+        let other = db::add_transaction(&conn, -100.0, "Shopping", "Shoes", Some("2024-03-01".to_string()), None);
+        let postings = post_due(&conn, &rules, date(2024, 3, 5), false);
+        assert!(!postings[0].matched);
+        assert_ne!(postings[0].transaction_id, Some(other));
+    }
+
+    #[test]
+    fn replaces_a_posted_instance_only_with_the_same_payment() {
+        let conn = db::test_db();
+        let rules = rent(&conn);
+        let posted = post_due(&conn, &rules, date(2024, 3, 5), false)[0].transaction_id.unwrap();
+
+        let last_id = posted;
+        db::add_transaction(&conn, -100.0, "Shopping", "Shoes", Some("2024-03-01".to_string()), None);
+        assert_eq!(replace_posted(&conn, last_id), 0);
+
+        let imported = db::add_transaction(&conn, -100.0, "Rent", "LANDLORD SRL", Some("2024-03-02".to_string()), None);
+        assert_eq!(replace_posted(&conn, last_id), 1);
+        let linked: i64 = conn
+            .query_row("SELECT transaction_id FROM recurring_posts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(linked, imported);
+    }
+}
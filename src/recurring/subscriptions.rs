@@ -0,0 +1,91 @@
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use crate::output::Table;
+use crate::reports::is_transfer;
+use super::detect::{self, Recurring};
+
+/// Recurring charges, largest yearly cost first. Transfers between your own
+/// accounts and recurring income aren't subscriptions.
+pub fn subscriptions(conn: &Connection) -> Vec<Recurring> {
+    let mut found: Vec<Recurring> = detect::detect(conn)
+        .into_iter()
+        .filter(|r| r.amount() < 0.0 && !r.category.as_deref().is_some_and(is_transfer))
+        .collect();
+    found.sort_by(|a, b| annual_cost(b).total_cmp(&annual_cost(a)));
+    found
+}
+
+/// What a subscription costs over a year at its current price.
+pub fn annual_cost(subscription: &Recurring) -> f64 {
+    -subscription.amount() * subscription.cadence.per_year()
+}
+
+fn status(subscription: &Recurring, today: NaiveDate) -> &'static str {
+    if subscription.is_stale(today) {
+        "stopped"
+    } else if subscription.is_overdue(today) {
+        "overdue"
+    } else {
+        "active"
+    }
+}
+
+pub fn subscriptions_table(subscriptions: &[Recurring], today: NaiveDate) -> Table {
+    let active: Vec<&Recurring> = subscriptions.iter().filter(|s| !s.is_stale(today)).collect();
+    let mut table = Table::new(
+        "subscriptions",
+        &["payee", "category", "account", "cadence", "typical", "last_amount", "annual_cost", "charges", "last_date", "next_expected", "status"],
+    )
+    .with_title(format!(
+        "{} active subscription(s), {:.2} a year",
+        active.len(),
+        active.iter().map(|s| annual_cost(s)).sum::<f64>()
+    ));
+
+    for s in subscriptions {
+        table.push(vec![
+            s.description.as_str().into(),
+            s.category.clone().into(),
+            s.account.clone().into(),
+            s.cadence.as_str().into(),
+            (-s.typical()).into(),
+            (-s.amount()).into(),
+            annual_cost(s).into(),
+            s.dates.len().into(),
+            s.last_date().to_string().into(),
+            s.next_expected().to_string().into(),
+            status(s, today).into(),
+        ]);
+    }
+    table
+}
+
+/// Price increases, missed and duplicate charges, and overdue payments,
+/// newest first.
+pub fn issues_table(subscriptions: &[Recurring], today: NaiveDate) -> Table {
+    let mut issues: Vec<(NaiveDate, &str, &str, String)> = Vec::new();
+    for s in subscriptions {
+        let payee = s.description.as_str();
+        if let Some((date, old, new)) = s.price_increase() {
+            let pct = (new.abs() - old.abs()) / old.abs() * 100.0;
+            issues.push((date, payee, "price increase", format!("{:.2} -> {:.2} (+{:.1}%)", -old, -new, pct)));
+        }
+        for date in &s.missed {
+            issues.push((*date, payee, "missed", "no charge around this date".to_string()));
+        }
+        for (date, count) in &s.duplicates {
+            issues.push((*date, payee, "duplicate", format!("charged {} times", count)));
+        }
+        if s.is_overdue(today) && !s.is_stale(today) {
+            issues.push((s.next_expected(), payee, "overdue", format!("not charged since {}", s.last_date())));
+        }
+    }
+    issues.sort_by_key(|i| std::cmp::Reverse(i.0));
+
+    let mut table = Table::new("subscription_issues", &["date", "payee", "issue", "detail"])
+        .with_title("Subscription issues");
+    for (date, payee, issue, detail) in issues {
+        table.push(vec![date.to_string().into(), payee.into(), issue.into(), detail.into()]);
+    }
+    table
+}
@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use crate::output::Table;
+use crate::stats::median;
+use super::{expenses, payee_key, Expense};
+use super::series::GroupBy;
+
+/// Earlier charges (or months) needed before anything is compared with them.
+const MIN_HISTORY: usize = 4;
+const MIN_MONTHS: usize = 3;
+/// Months of history a category's month is compared with.
+const BASELINE_MONTHS: usize = 12;
+/// Anything flagged must also be at least this many times the typical amount,
+/// so very steady payees don't flag small changes.
+const MIN_RATIO: f64 = 1.5;
+
+/// A transaction or category month well above its usual amount.
+pub struct Anomaly {
+    pub date: NaiveDate,
+    /// `payee`, `category` (a charge compared with its category) or `month`
+    pub kind: &'static str,
+    pub subject: String,
+    pub amount: f64,
+    pub typical: f64,
+    pub score: f64,
+    pub explanation: String,
+}
+
+/// The median of `history` and how many robust standard deviations `value`
+/// lies above it (the modified z-score, using the median absolute
+/// deviation). When the history barely varies, 5% of the median stands in
+/// for the deviation so a steady payee isn't flagged for pennies.
+fn robust_score(value: f64, history: &[f64]) -> (f64, f64) {
+    let typical = median(history);
+    let mad = median(&history.iter().map(|h| (h - typical).abs()).collect::<Vec<_>>());
+    let scale = (1.4826 * mad).max(typical * 0.05).max(0.01);
+    (typical, (value - typical) / scale)
+}
+
+fn flagged(value: f64, typical: f64, score: f64, threshold: f64) -> bool {
+    typical > 0.0 && score >= threshold && value >= typical * MIN_RATIO
+}
+
+/// Compares the spending transactions accepted by `include` with earlier
+/// charges at the same payee (or, without enough of those, in the same
+/// category), and the months those transactions fall in with each
+/// category's previous months. Scores at or above `threshold` are reported.
+pub fn find_anomalies(
+    conn: &Connection,
+    threshold: f64,
+    today: NaiveDate,
+    include: impl Fn(i64, NaiveDate) -> bool,
+) -> Vec<Anomaly> {
+    let spending = expenses(conn, None);
+    let mut anomalies = Vec::new();
+    let mut months = BTreeSet::new();
+    // Earlier amounts by payee and by category, filled in as the expenses
+    // are walked oldest first.
+    let mut payee_history: HashMap<String, Vec<f64>> = HashMap::new();
+    let mut category_history: HashMap<&str, Vec<f64>> = HashMap::new();
+
+    for spend in &spending {
+        let payee = (!spend.description.is_empty()).then(|| payee_key(&spend.description));
+
+        if include(spend.id, spend.date) {
+            months.insert(GroupBy::Month.bucket_start(spend.date));
+
+            let by_payee = payee.as_ref().and_then(|p| payee_history.get(p)).filter(|h| h.len() >= MIN_HISTORY);
+            let by_category = spend.category.as_deref().and_then(|c| category_history.get(c)).filter(|h| h.len() >= MIN_HISTORY);
+            let found = by_payee.map(|h| ("payee", h)).or(by_category.map(|h| ("category", h)));
+            if let Some((kind, history)) = found {
+                anomalies.extend(charge_anomaly(spend, kind, history, threshold));
+            }
+        }
+
+        if let Some(payee) = payee {
+            payee_history.entry(payee).or_default().push(spend.amount);
+        }
+        if let Some(category) = spend.category.as_deref() {
+            category_history.entry(category).or_default().push(spend.amount);
+        }
+    }
+
+    anomalies.extend(month_anomalies(&spending, &months, threshold, today));
+    anomalies.sort_by_key(|a| std::cmp::Reverse(a.date));
+    anomalies
+}
+
+/// `spend` compared with earlier charges at its payee or in its category.
+fn charge_anomaly(spend: &Expense, kind: &'static str, history: &[f64], threshold: f64) -> Option<Anomaly> {
+    let (typical, score) = robust_score(spend.amount, history);
+    if !flagged(spend.amount, typical, score, threshold) {
+        return None;
+    }
+
+    let (subject, baseline) = match kind {
+        "payee" => (spend.description.clone(), format!("{} earlier charges there", history.len())),
+        _ => {
+            let category = spend.category.clone().unwrap_or_default();
+            let baseline = format!("{} earlier charges in {}", history.len(), category);
+            (category, baseline)
+        }
+    };
+    Some(Anomaly {
+        date: spend.date,
+        kind,
+        subject,
+        amount: spend.amount,
+        typical,
+        score,
+        explanation: format!(
+            "{:.2} at {} is {:.1}x the usual {:.2} (median of {})",
+            spend.amount,
+            if spend.description.is_empty() { "an unnamed payee" } else { &spend.description },
+            spend.amount / typical,
+            typical,
+            baseline
+        ),
+    })
+}
+
+/// Category totals in each of `months` compared with the category's
+/// previous months, counting months without spending as zero once the
+/// category has been used. The current month counts up to today.
+fn month_anomalies(spending: &[Expense], months: &BTreeSet<NaiveDate>, threshold: f64, today: NaiveDate) -> Vec<Anomaly> {
+    let mut totals: BTreeMap<&str, BTreeMap<NaiveDate, f64>> = BTreeMap::new();
+    for spend in spending {
+        if let Some(category) = spend.category.as_deref() {
+            *totals.entry(category).or_default().entry(GroupBy::Month.bucket_start(spend.date)).or_default() += spend.amount;
+        }
+    }
+
+    let mut anomalies = Vec::new();
+    for (category, by_month) in &totals {
+        let first = *by_month.keys().next().unwrap();
+        for month in months {
+            let Some(total) = by_month.get(month).copied() else { continue };
+            let history: Vec<f64> = (1..=BASELINE_MONTHS as u32)
+                .filter_map(|n| month.checked_sub_months(chrono::Months::new(n)))
+                .filter(|m| *m >= first)
+                .map(|m| by_month.get(&m).copied().unwrap_or(0.0))
+                .collect();
+            if history.len() < MIN_MONTHS {
+                continue;
+            }
+
+            let (typical, score) = robust_score(total, &history);
+            if flagged(total, typical, score, threshold) {
+                let so_far = if GroupBy::Month.bucket_start(today) == *month { " so far" } else { "" };
+                anomalies.push(Anomaly {
+                    date: *month,
+                    kind: "month",
+                    subject: category.to_string(),
+                    amount: total,
+                    typical,
+                    score,
+                    explanation: format!(
+                        "{} spending in {} is {:.2}{}, {:.1}x the usual {:.2} a month (median of the previous {} months)",
+                        category,
+                        GroupBy::Month.label(*month),
+                        total,
+                        so_far,
+                        total / typical,
+                        typical,
+                        history.len()
+                    ),
+                });
+            }
+        }
+    }
+    anomalies
+}
+
+pub fn anomalies_table(anomalies: &[Anomaly], title: String) -> Table {
+    let mut table = Table::new("anomalies", &["date", "kind", "subject", "amount", "typical", "score", "explanation"])
+        .with_title(title);
+    for a in anomalies {
+        table.push(vec![
+            a.date.to_string().into(),
+            a.kind.into(),
+            a.subject.as_str().into(),
+            a.amount.into(),
+            a.typical.into(),
+            a.score.into(),
+            a.explanation.as_str().into(),
+        ]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_by_median_absolute_deviation() {
+        // Median 100, deviations 10, 10, 0, 10, 10 → MAD 10.
+        let (typical, score) = robust_score(148.26 + 100.0, &[90.0, 110.0, 100.0, 90.0, 110.0]);
+        assert_eq!(typical, 100.0);
+        assert!((score - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn steady_history_falls_back_to_five_percent_of_the_median() {
+        let (typical, score) = robust_score(60.0, &[40.0, 40.0, 40.0, 40.0]);
+        assert_eq!(typical, 40.0);
+        assert!((score - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn payees_match_whatever_the_case() {
+        let conn = crate::db::test_db();
+        for (i, name) in ["Cafenea Ștefan", "CAFENEA ȘTEFAN", "cafenea ștefan", "Cafenea Ștefan"].iter().enumerate() {
+            crate::db::add_transaction(&conn, -10.0 - i as f64, "", name, Some(format!("2024-03-0{}", i + 1)), None);
+        }
+        crate::db::add_transaction(&conn, -80.0, "", "CAFENEA ȘTEFAN", Some("2024-03-09".to_string()), None);
+
+        let today = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap();
+        let anomalies = find_anomalies(&conn, 3.5, today, |_, _| true);
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].kind, "payee");
+        assert_eq!(anomalies[0].amount, 80.0);
+    }
+
+    #[test]
+    fn flags_need_a_large_enough_ratio() {
+        assert!(flagged(60.0, 40.0, 10.0, 3.5));
+        assert!(!flagged(55.0, 40.0, 10.0, 3.5));
+        assert!(!flagged(60.0, 40.0, 3.0, 3.5));
+    }
+}
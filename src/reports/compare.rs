@@ -0,0 +1,63 @@
+use rusqlite::Connection;
+use crate::output::Table;
+use super::range::DateRange;
+use super::{category_lines, CategoryLine};
+
+fn change_pct(current: f64, previous: f64) -> Option<f64> {
+    (previous != 0.0).then(|| (current - previous) / previous.abs() * 100.0)
+}
+
+/// Lines up every category in `base` and each of `others`, with the change
+/// from each other period to `base`, followed by the `top` largest movers
+/// against the first other period.
+pub fn compare(conn: &Connection, base: DateRange, others: &[DateRange], top: usize) -> Vec<Table> {
+    let mut periods = vec![base];
+    periods.extend_from_slice(others);
+    let lines = category_lines(conn, &periods);
+
+    let mut columns = vec!["category".to_string(), "type".to_string(), base.label()];
+    for other in others {
+        columns.push(other.label());
+        columns.push(format!("change_vs_{}", other.label()));
+        columns.push(format!("change_pct_vs_{}", other.label()));
+    }
+    let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+
+    let mut table = Table::new("comparison", &columns).with_title(format!(
+        "{} compared with {}",
+        base.label(),
+        others.iter().map(DateRange::label).collect::<Vec<_>>().join(", ")
+    ));
+    for line in &lines {
+        let mut row = vec![line.category.clone().into(), line.kind.into(), line.amounts[0].into()];
+        for previous in &line.amounts[1..] {
+            row.push((*previous).into());
+            row.push((line.amounts[0] - previous).into());
+            row.push(change_pct(line.amounts[0], *previous).into());
+        }
+        table.push(row);
+    }
+
+    let mut tables = vec![table];
+    if let Some(first) = others.first() {
+        let mut movers: Vec<&CategoryLine> = lines.iter().filter(|l| l.amounts[0] != l.amounts[1]).collect();
+        movers.sort_by(|a, b| (b.amounts[0] - b.amounts[1]).abs().total_cmp(&(a.amounts[0] - a.amounts[1]).abs()));
+
+        let mut table = Table::new("movers", &["category", "type", "previous", "current", "change", "change_pct", "direction"])
+            .with_title(format!("Largest movers vs {}", first.label()));
+        for line in movers.into_iter().take(top) {
+            let change = line.amounts[0] - line.amounts[1];
+            table.push(vec![
+                line.category.clone().into(),
+                line.kind.into(),
+                line.amounts[1].into(),
+                line.amounts[0].into(),
+                change.into(),
+                change_pct(line.amounts[0], line.amounts[1]).into(),
+                if change > 0.0 { "up" } else { "down" }.into(),
+            ]);
+        }
+        tables.push(table);
+    }
+    tables
+}
@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use chrono::Datelike;
+use rusqlite::Connection;
+use crate::output::Table;
+use super::{expenses, payee_key, Expense, WEEKDAYS};
+use super::range::DateRange;
+
+const BAR_WIDTH: f64 = 30.0;
+
+fn bar(value: f64, widest: f64) -> String {
+    let width = if widest > 0.0 { (value / widest * BAR_WIDTH).round() as usize } else { 0 };
+    "█".repeat(width)
+}
+
+#[derive(Default)]
+struct Payee {
+    name: String,
+    visits: usize,
+    spent: f64,
+}
+
+/// The `top` payees by money spent and by number of visits. Payees are
+/// descriptions, matched case-insensitively.
+pub fn top_payees(expenses: &[Expense], top: usize, range: DateRange) -> Vec<Table> {
+    let mut payees: BTreeMap<String, Payee> = BTreeMap::new();
+    for e in expenses.iter().filter(|e| !e.description.is_empty()) {
+        let payee = payees.entry(payee_key(&e.description)).or_default();
+        payee.name = e.description.clone();
+        payee.visits += 1;
+        payee.spent += e.amount;
+    }
+    let total: f64 = expenses.iter().map(|e| e.amount).sum();
+    let mut payees: Vec<Payee> = payees.into_values().collect();
+
+    let table = |name: &str, title: String, payees: &[Payee]| {
+        let mut table = Table::new(name, &["payee", "visits", "spent", "average", "share_pct"]).with_title(title);
+        for p in payees.iter().take(top) {
+            table.push(vec![
+                p.name.as_str().into(),
+                p.visits.into(),
+                p.spent.into(),
+                (p.spent / p.visits as f64).into(),
+                (total > 0.0).then(|| p.spent / total * 100.0).into(),
+            ]);
+        }
+        table
+    };
+
+    payees.sort_by(|a, b| b.spent.total_cmp(&a.spent));
+    let by_spend = table("top_payees", format!("Top payees by spend for {}", range.label()), &payees);
+    payees.sort_by(|a, b| b.visits.cmp(&a.visits).then(b.spent.total_cmp(&a.spent)));
+    let by_visits = table("frequent_payees", format!("Most visited payees for {}", range.label()), &payees);
+    vec![by_spend, by_visits]
+}
+
+pub fn largest_transactions(expenses: &[Expense], top: usize, range: DateRange) -> Table {
+    let mut largest: Vec<&Expense> = expenses.iter().collect();
+    largest.sort_by(|a, b| b.amount.total_cmp(&a.amount));
+
+    let mut table = Table::new("largest", &["id", "date", "amount", "category", "description"])
+        .with_title(format!("Largest expenses for {}", range.label()));
+    for e in largest.into_iter().take(top) {
+        table.push(vec![
+            e.id.into(),
+            e.date.to_string().into(),
+            e.amount.into(),
+            e.category.clone().into(),
+            e.description.as_str().into(),
+        ]);
+    }
+    table
+}
+
+/// Number of expenses, average and largest ticket per category, by average.
+pub fn ticket_sizes(expenses: &[Expense], range: DateRange) -> Table {
+    let mut categories: BTreeMap<Option<String>, (usize, f64, f64)> = BTreeMap::new();
+    for e in expenses {
+        let (count, spent, largest) = categories.entry(e.category.clone()).or_default();
+        *count += 1;
+        *spent += e.amount;
+        *largest = largest.max(e.amount);
+    }
+    let mut rows: Vec<_> = categories.into_iter().collect();
+    rows.sort_by(|a, b| (b.1.1 / b.1.0 as f64).total_cmp(&(a.1.1 / a.1.0 as f64)));
+
+    let mut table = Table::new("ticket_size", &["category", "transactions", "spent", "average", "largest"])
+        .with_title(format!("Average ticket size by category for {}", range.label()));
+    for (category, (count, spent, largest)) in rows {
+        table.push(vec![category.into(), count.into(), spent.into(), (spent / count as f64).into(), largest.into()]);
+    }
+    table
+}
+
+/// Spending per day of the week, with the average per calendar day so
+/// weekdays that occur more often in the range don't stand out.
+pub fn by_weekday(expenses: &[Expense], range: DateRange) -> Table {
+    let mut spent = [0.0; 7];
+    let mut count = [0usize; 7];
+    for e in expenses {
+        let day = e.date.weekday().num_days_from_monday() as usize;
+        spent[day] += e.amount;
+        count[day] += 1;
+    }
+    let mut days = [0usize; 7];
+    for date in range.from.iter_days().take_while(|d| *d <= range.to) {
+        days[date.weekday().num_days_from_monday() as usize] += 1;
+    }
+    let total: f64 = spent.iter().sum();
+    let widest = spent.iter().copied().fold(0.0, f64::max);
+
+    let mut table = Table::new("by_weekday", &["weekday", "transactions", "spent", "per_day", "share_pct", "chart"])
+        .with_title(format!("Spending by day of the week for {}", range.label()));
+    for (i, name) in WEEKDAYS.iter().enumerate() {
+        table.push(vec![
+            (*name).into(),
+            count[i].into(),
+            spent[i].into(),
+            (days[i] > 0).then(|| spent[i] / days[i] as f64).into(),
+            (total > 0.0).then(|| spent[i] / total * 100.0).into(),
+            bar(spent[i], widest).into(),
+        ]);
+    }
+    table
+}
+
+pub fn by_day_of_month(expenses: &[Expense], range: DateRange) -> Table {
+    let mut spent = [0.0; 31];
+    let mut count = [0usize; 31];
+    for e in expenses {
+        spent[e.date.day0() as usize] += e.amount;
+        count[e.date.day0() as usize] += 1;
+    }
+    let total: f64 = spent.iter().sum();
+    let widest = spent.iter().copied().fold(0.0, f64::max);
+
+    let mut table = Table::new("by_day_of_month", &["day", "transactions", "spent", "share_pct", "chart"])
+        .with_title(format!("Spending by day of the month for {}", range.label()));
+    for day in 0..31 {
+        table.push(vec![
+            (day + 1).into(),
+            count[day].into(),
+            spent[day].into(),
+            (total > 0.0).then(|| spent[day] / total * 100.0).into(),
+            bar(spent[day], widest).into(),
+        ]);
+    }
+    table
+}
+
+/// All distribution sections for `range`, with `top` rows in the ranked ones.
+pub fn distribution(conn: &Connection, range: DateRange, top: usize) -> Vec<Table> {
+    let expenses = expenses(conn, Some(range));
+    let mut tables = top_payees(&expenses, top, range);
+    tables.push(largest_transactions(&expenses, top, range));
+    tables.push(ticket_sizes(&expenses, range));
+    tables.push(by_weekday(&expenses, range));
+    tables.push(by_day_of_month(&expenses, range));
+    tables
+}
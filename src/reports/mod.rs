@@ -1,35 +1,272 @@
+pub mod anomalies;
+pub mod compare;
+pub mod distribution;
+pub mod pivot;
+pub mod range;
+pub mod series;
+pub mod trend;
+
+use std::collections::BTreeMap;
+use chrono::NaiveDate;
 use rusqlite::Connection;
+use crate::db::ISO_DATE;
+use crate::output::Table;
+use range::DateRange;
+
+/// A category's amounts over a range. Money in and money out are kept apart
+/// so uncategorized income and spending don't cancel out.
+pub struct CategoryTotal {
+    pub category: Option<String>,
+    /// Net amount: negative for spending, positive for income
+    pub total: f64,
+    /// Sum of the positive amounts
+    pub inflow: f64,
+    /// Sum of the negative amounts, as a positive number
+    pub outflow: f64,
+}
+
+impl CategoryTotal {
+    pub fn is_transfer(&self) -> bool {
+        self.category.as_deref().is_some_and(is_transfer)
+    }
+}
+
+/// Money moved between your own accounts is categorized as `Transfer`,
+/// `Transfers` or `Transfer:<something>` and counts as neither income nor
+/// spending.
+pub fn is_transfer(category: &str) -> bool {
+    let category = category.to_lowercase();
+    category == "transfer" || category == "transfers" || category.starts_with("transfer:")
+}
+
+pub const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+/// One expense, as a positive amount.
+pub struct Expense {
+    pub id: i64,
+    pub date: NaiveDate,
+    pub description: String,
+    pub category: Option<String>,
+    pub amount: f64,
+}
+
+/// The key transactions are grouped by payee with, so the same payee written
+/// in different case (including non-ASCII letters) is counted once.
+pub fn payee_key(description: &str) -> String {
+    description.trim().to_lowercase()
+}
+
+/// Every expense in `range` (all of them with `None`), oldest first.
+/// Income, refunds and transfers are left out.
+pub fn expenses(conn: &Connection, range: Option<DateRange>) -> Vec<Expense> {
+    let (from, to) = range.map_or((None, None), |r| (Some(r.from.to_string()), Some(r.to.to_string())));
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, {date} AS day, COALESCE(TRIM(description), ''), NULLIF(category, ''), -amount
+         FROM transactions
+         WHERE amount < 0 AND (?1 IS NULL OR {date} BETWEEN ?1 AND ?2)
+         ORDER BY day, id",
+        date = ISO_DATE
+    )).unwrap();
+    let rows = stmt.query_map([from, to], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get(2)?, row.get::<_, Option<String>>(3)?, row.get(4)?))
+    }).unwrap();
+
+    rows.flatten()
+        .filter(|row| !row.3.as_deref().is_some_and(is_transfer))
+        .filter_map(|(id, day, description, category, amount)| {
+            let date = NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok()?;
+            Some(Expense { id, date, description, category, amount })
+        })
+        .collect()
+}
+
+const CATEGORY_SUMS: &str = "NULLIF(category, ''), SUM(amount),
+         SUM(CASE WHEN amount > 0 THEN amount ELSE 0 END), SUM(CASE WHEN amount < 0 THEN -amount ELSE 0 END)";
+
+fn category_total(row: &rusqlite::Row, offset: usize) -> rusqlite::Result<CategoryTotal> {
+    Ok(CategoryTotal {
+        category: row.get(offset)?,
+        total: row.get(offset + 1)?,
+        inflow: row.get(offset + 2)?,
+        outflow: row.get(offset + 3)?,
+    })
+}
+
+pub fn category_totals(conn: &Connection, range: DateRange) -> Vec<CategoryTotal> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {sums} FROM transactions
+         WHERE {date} BETWEEN ?1 AND ?2
+         GROUP BY NULLIF(category, '')
+         ORDER BY NULLIF(category, '')",
+        sums = CATEGORY_SUMS,
+        date = ISO_DATE
+    )).unwrap();
 
-pub fn monthly_spending(conn: &Connection, month: &str, year: &str) -> f64 {
-    let mut stmt = conn.prepare(
-        "SELECT SUM(amount) FROM transactions
-         WHERE substr(date, 1, 2) = ?1
-         AND substr(date, -4) = ?2"
-    ).unwrap();
-
-    stmt.query_row([month, year], |row| {
-        Ok(row.get::<_, Option<f64>>(0)?.unwrap_or(0.0).abs())
-    }).unwrap()
-}
-
-pub fn category_breakdown(conn: &Connection, month: &str, year: &str) {
-    let mut stmt = conn.prepare(
-        "SELECT category, SUM(amount) FROM transactions
-         WHERE substr(date, 1, 2) = ?1
-         AND substr(date, -4) = ?2
-         GROUP BY category"
-    ).unwrap();
-
-    let rows = stmt.query_map([month, year], |row| {
-        let category: String = row.get(0)?;
-        let total: f64 = row.get::<_, Option<f64>>(1)?.unwrap_or(0.0).abs();
-        Ok((category, total))
+    let rows = stmt.query_map([range.from.to_string(), range.to.to_string()], |row| category_total(row, 0)).unwrap();
+    rows.flatten().collect()
+}
+
+/// Category totals for each day in `range` that has transactions, oldest
+/// first, so callers can fold them into buckets without a query per bucket.
+pub fn daily_category_totals(conn: &Connection, range: DateRange) -> Vec<(NaiveDate, CategoryTotal)> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {date} AS day, {sums} FROM transactions
+         WHERE {date} BETWEEN ?1 AND ?2
+         GROUP BY day, NULLIF(category, '')
+         ORDER BY day",
+        sums = CATEGORY_SUMS,
+        date = ISO_DATE
+    )).unwrap();
+
+    let rows = stmt.query_map([range.from.to_string(), range.to.to_string()], |row| {
+        Ok((row.get::<_, String>(0)?, category_total(row, 1)?))
     }).unwrap();
+    rows.flatten()
+        .filter_map(|(day, total)| Some((NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok()?, total)))
+        .collect()
+}
+
+/// One category's amounts over several periods, in the order given.
+/// Amounts are positive in the category's usual direction: money spent for
+/// expenses, money received for income.
+pub struct CategoryLine {
+    pub category: Option<String>,
+    /// `expense`, `income` or `transfer`
+    pub kind: &'static str,
+    pub amounts: Vec<f64>,
+}
+
+pub fn category_lines(conn: &Connection, periods: &[DateRange]) -> Vec<CategoryLine> {
+    let mut totals: BTreeMap<Option<String>, Vec<f64>> = BTreeMap::new();
+    for (i, period) in periods.iter().enumerate() {
+        for c in category_totals(conn, *period) {
+            totals.entry(c.category).or_insert_with(|| vec![0.0; periods.len()])[i] = c.total;
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|(category, signed)| {
+            // The first period with activity decides whether it's income.
+            let first = signed.iter().copied().find(|t| *t != 0.0).unwrap_or(0.0);
+            let kind = if category.as_deref().is_some_and(is_transfer) {
+                "transfer"
+            } else if first > 0.0 {
+                "income"
+            } else {
+                "expense"
+            };
+            // `0.0 - t` rather than `-t` so empty periods aren't shown as -0.00.
+            let amounts = if kind == "expense" {
+                signed.iter().map(|t| 0.0 - t).collect()
+            } else {
+                signed
+            };
+            CategoryLine { category, kind, amounts }
+        })
+        .collect()
+}
+
+
+/// Income, expenses and transfers over a range.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Totals {
+    pub income: f64,
+    pub expenses: f64,
+    /// Net amount transferred in (negative when more went out)
+    pub transfers: f64,
+}
+
+impl Totals {
+    pub fn from_categories(categories: &[CategoryTotal]) -> Self {
+        let mut totals = Totals::default();
+        for c in categories {
+            totals.add(c);
+        }
+        totals
+    }
+
+    /// Counts every positive amount as income and every negative one as
+    /// spending, whatever the category's net.
+    pub fn add(&mut self, c: &CategoryTotal) {
+        if c.is_transfer() {
+            self.transfers += c.total;
+        } else {
+            self.income += c.inflow;
+            self.expenses += c.outflow;
+        }
+    }
+
+    pub fn net(&self) -> f64 {
+        self.income - self.expenses
+    }
+
+    /// Share of income that wasn't spent, in percent; `None` without income.
+    pub fn savings_rate(&self) -> Option<f64> {
+        (self.income > 0.0).then(|| self.net() / self.income * 100.0)
+    }
+}
+
+pub fn summary_table(range: DateRange, totals: &Totals) -> Table {
+    let mut table = Table::new("summary", &[
+        "period", "from", "to", "income", "expenses", "transfers", "net", "savings_rate",
+    ]);
+    table.push(vec![
+        range.label().into(),
+        range.from.to_string().into(),
+        range.to.to_string().into(),
+        totals.income.into(),
+        totals.expenses.into(),
+        totals.transfers.into(),
+        totals.net().into(),
+        totals.savings_rate().into(),
+    ]);
+    table
+}
+
+fn share_table(name: &str, title: String, rows: Vec<(Option<String>, f64)>) -> Table {
+    let total: f64 = rows.iter().map(|(_, amount)| amount).sum();
+    let mut table = Table::new(name, &["category", "amount", "share_pct"]).with_title(title);
+    for (category, amount) in rows {
+        let share = (total != 0.0).then(|| amount / total * 100.0);
+        table.push(vec![category.into(), amount.into(), share.into()]);
+    }
+    table
+}
+
+/// Expenses and income by category, largest first, each with its share of
+/// the total; a category with both appears in both. Transfers get a table
+/// of their own when there are any.
+pub fn category_breakdown(categories: &[CategoryTotal], range: DateRange) -> Vec<Table> {
+    let mut expenses = Vec::new();
+    let mut income = Vec::new();
+    let mut transfers = Vec::new();
+    for c in categories {
+        if c.is_transfer() {
+            transfers.push((c.category.clone(), c.total));
+            continue;
+        }
+        if c.inflow > 0.0 {
+            income.push((c.category.clone(), c.inflow));
+        }
+        if c.outflow > 0.0 {
+            expenses.push((c.category.clone(), c.outflow));
+        }
+    }
+    expenses.sort_by(|a, b| b.1.total_cmp(&a.1));
+    income.sort_by(|a, b| b.1.total_cmp(&a.1));
 
-    println!("Category breakdown for {}/{}:", month, year);
-    for row in rows {
-        if let Ok((category, total)) = row {
-            println!(" - {}: {} lei", category, total);
+    let mut tables = vec![
+        share_table("expenses", format!("Expenses by category for {}", range.label()), expenses),
+        share_table("income", format!("Income by category for {}", range.label()), income),
+    ];
+    if !transfers.is_empty() {
+        let mut table = Table::new("transfers", &["category", "amount"])
+            .with_title(format!("Transfers for {}", range.label()));
+        for (category, amount) in transfers {
+            table.push(vec![category.into(), amount.into()]);
         }
+        tables.push(table);
     }
+    tables
 }
@@ -0,0 +1,356 @@
+use std::collections::{BTreeMap, BTreeSet};
+use chrono::{Datelike, NaiveDate};
+use clap::ValueEnum;
+use rusqlite::Connection;
+use crate::db::ISO_DATE;
+use crate::output::{Table, Value};
+use super::range::DateRange;
+use super::series::GroupBy;
+use super::WEEKDAYS;
+
+const NONE: &str = "(none)";
+const TOTAL: &str = "total";
+
+/// What transactions are grouped by along a pivot axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Dimension {
+    Category,
+    /// The description without its tags
+    Payee,
+    /// Transactions without an account belong to the default account
+    Account,
+    /// `#tags` in the description; a transaction counts once under each tag
+    Tag,
+    Weekday,
+    Month,
+}
+
+impl Dimension {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Dimension::Category => "category",
+            Dimension::Payee => "payee",
+            Dimension::Account => "account",
+            Dimension::Tag => "tag",
+            Dimension::Weekday => "weekday",
+            Dimension::Month => "month",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Dimension::from_str(s, true).ok()
+    }
+}
+
+/// How the amounts in a pivot cell are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Aggregate {
+    Sum,
+    Count,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Aggregate::Sum => "sum",
+            Aggregate::Count => "count",
+            Aggregate::Avg => "avg",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+        }
+    }
+
+    fn apply(&self, amounts: &[f64]) -> Value {
+        if amounts.is_empty() {
+            return match self {
+                Aggregate::Count => 0usize.into(),
+                _ => Value::Null,
+            };
+        }
+        match self {
+            Aggregate::Sum => amounts.iter().sum::<f64>().into(),
+            Aggregate::Count => amounts.len().into(),
+            Aggregate::Avg => (amounts.iter().sum::<f64>() / amounts.len() as f64).into(),
+            Aggregate::Min => amounts.iter().copied().fold(f64::INFINITY, f64::min).into(),
+            Aggregate::Max => amounts.iter().copied().fold(f64::NEG_INFINITY, f64::max).into(),
+        }
+    }
+}
+
+struct Row {
+    amount: f64,
+    date: NaiveDate,
+    category: Option<String>,
+    payee: String,
+    account: Option<String>,
+    tags: Vec<String>,
+}
+
+impl Row {
+    /// The row's keys along `dimension`; more than one only for tags.
+    fn keys(&self, dimension: Dimension) -> Vec<Key> {
+        let text = |value: Option<&str>| Key { order: 0, label: value.unwrap_or(NONE).to_string() };
+        match dimension {
+            Dimension::Category => vec![text(self.category.as_deref())],
+            Dimension::Payee => vec![text(Some(&self.payee).filter(|p| !p.is_empty()).map(String::as_str))],
+            Dimension::Account => vec![text(self.account.as_deref())],
+            Dimension::Tag if self.tags.is_empty() => vec![text(None)],
+            Dimension::Tag => self.tags.iter().map(|t| text(Some(t))).collect(),
+            Dimension::Weekday => {
+                let day = self.date.weekday().num_days_from_monday();
+                vec![Key { order: day, label: WEEKDAYS[day as usize].to_string() }]
+            }
+            Dimension::Month => vec![text(Some(&GroupBy::Month.label(self.date)))],
+        }
+    }
+}
+
+/// A pivot heading. Weekdays sort by `order`, everything else by label, with
+/// `(none)` last.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Key {
+    order: u32,
+    label: String,
+}
+
+impl Key {
+    fn sort_key(&self) -> (bool, u32, String) {
+        (self.label == NONE, self.order, self.label.to_lowercase())
+    }
+}
+
+/// `label`, with ` (dimension)` appended until it differs from every heading
+/// in `taken`, so data can't collide with the totals or the row header.
+fn unique_heading(label: &str, dimension: Dimension, taken: &mut BTreeSet<String>) -> String {
+    let mut heading = label.to_string();
+    while taken.contains(&heading) {
+        heading = format!("{} ({})", heading, dimension.as_str());
+    }
+    taken.insert(heading.clone());
+    heading
+}
+
+/// Splits `"Lunch #work #team"` into the payee and its tags.
+fn split_tags(description: &str) -> (String, Vec<String>) {
+    let mut words = Vec::new();
+    let mut tags = Vec::new();
+    for word in description.split_whitespace() {
+        match word.strip_prefix('#') {
+            Some(tag) if !tag.is_empty() => tags.push(tag.to_string()),
+            _ => words.push(word),
+        }
+    }
+    (words.join(" "), tags)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Contains,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A `--filter` condition such as `category=Food,Groceries`, `payee~uber`,
+/// `account!=Card` or `amount<0`.
+pub struct Filter {
+    /// `None` for `amount`
+    field: Option<Dimension>,
+    op: Op,
+    value: String,
+}
+
+impl Filter {
+    pub fn parse(text: &str) -> Option<Self> {
+        // Two-character operators first, so `<=` isn't read as `<`.
+        const OPS: [(&str, Op); 7] =
+            [("!=", Op::Ne), ("<=", Op::Le), (">=", Op::Ge), ("=", Op::Eq), ("~", Op::Contains), ("<", Op::Lt), (">", Op::Gt)];
+        let (at, token, op) = OPS.iter().filter_map(|(token, op)| text.find(token).map(|at| (at, *token, *op))).min_by_key(|f| f.0)?;
+        let (name, value) = (text[..at].trim(), text[at + token.len()..].trim());
+
+        let field = match name.to_lowercase().as_str() {
+            "amount" => {
+                if op == Op::Contains {
+                    return None;
+                }
+                value.parse::<f64>().ok()?;
+                None
+            }
+            other => {
+                if !matches!(op, Op::Eq | Op::Ne | Op::Contains) {
+                    return None;
+                }
+                Some(Dimension::parse(other)?)
+            }
+        };
+        Some(Filter { field, op, value: value.to_string() })
+    }
+
+    fn matches(&self, row: &Row) -> bool {
+        let Some(dimension) = self.field else {
+            let limit: f64 = self.value.parse().unwrap();
+            return match self.op {
+                Op::Eq => (row.amount - limit).abs() < 0.005,
+                Op::Ne => (row.amount - limit).abs() >= 0.005,
+                Op::Lt => row.amount < limit,
+                Op::Le => row.amount <= limit,
+                Op::Gt => row.amount > limit,
+                Op::Ge => row.amount >= limit,
+                Op::Contains => unreachable!("parse rejects `~` for amounts"),
+            };
+        };
+
+        let keys = row.keys(dimension);
+        let labels = keys.iter().map(|k| k.label.to_lowercase());
+        let value = self.value.to_lowercase();
+        let wanted: Vec<&str> = value.split(',').map(str::trim).collect();
+        match self.op {
+            Op::Eq => labels.into_iter().any(|l| wanted.contains(&l.as_str())),
+            Op::Ne => labels.into_iter().all(|l| !wanted.contains(&l.as_str())),
+            Op::Contains => labels.into_iter().any(|l| l.contains(&value)),
+            _ => unreachable!("parse only allows `=`, `!=` and `~` for text"),
+        }
+    }
+}
+
+fn load_rows(conn: &Connection, range: DateRange) -> Vec<Row> {
+    let default_account: Option<String> = conn
+        .query_row("SELECT name FROM accounts WHERE is_default", [], |row| row.get(0))
+        .ok();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT amount, {date} AS day, NULLIF(category, ''), COALESCE(description, ''), account
+         FROM transactions
+         WHERE {date} BETWEEN ?1 AND ?2
+         ORDER BY day, id",
+        date = ISO_DATE
+    )).unwrap();
+    let rows = stmt.query_map([range.from.to_string(), range.to.to_string()], |row| {
+        Ok((row.get::<_, f64>(0)?, row.get::<_, String>(1)?, row.get(2)?, row.get::<_, String>(3)?, row.get::<_, Option<String>>(4)?))
+    }).unwrap();
+
+    rows.flatten()
+        .filter_map(|(amount, day, category, description, account)| {
+            let (payee, tags) = split_tags(&description);
+            Some(Row {
+                amount,
+                date: NaiveDate::parse_from_str(&day, "%Y-%m-%d").ok()?,
+                category,
+                payee,
+                account: account.or_else(|| default_account.clone()),
+                tags,
+            })
+        })
+        .collect()
+}
+
+/// Aggregates the amounts of the transactions in `range` that pass every
+/// filter, one row per `rows` value and, with `cols`, one column per `cols`
+/// value, plus totals. Amounts keep their sign: expenses are negative.
+pub fn pivot(
+    conn: &Connection,
+    range: DateRange,
+    rows: Dimension,
+    cols: Option<Dimension>,
+    value: Aggregate,
+    filters: &[Filter],
+) -> Table {
+    let data: Vec<Row> = load_rows(conn, range).into_iter().filter(|r| filters.iter().all(|f| f.matches(r))).collect();
+
+    // Transactions are indexed so totals count a transaction once even when
+    // it sits under several tags.
+    let mut cells: BTreeMap<(Key, Key), Vec<f64>> = BTreeMap::new();
+    let mut row_members: BTreeMap<Key, BTreeSet<usize>> = BTreeMap::new();
+    let mut col_members: BTreeMap<Key, BTreeSet<usize>> = BTreeMap::new();
+    let all_key = Key { order: 0, label: TOTAL.to_string() };
+    for (i, r) in data.iter().enumerate() {
+        let col_keys = cols.map_or_else(|| vec![all_key.clone()], |c| r.keys(c));
+        for row_key in r.keys(rows) {
+            row_members.entry(row_key.clone()).or_default().insert(i);
+            for col_key in &col_keys {
+                cells.entry((row_key.clone(), col_key.clone())).or_default().push(r.amount);
+            }
+        }
+        for col_key in col_keys {
+            col_members.entry(col_key).or_default().insert(i);
+        }
+    }
+
+    let mut row_keys: Vec<&Key> = row_members.keys().collect();
+    row_keys.sort_by_key(|k| k.sort_key());
+    let mut col_keys: Vec<&Key> = col_members.keys().collect();
+    col_keys.sort_by_key(|k| k.sort_key());
+    let amounts = |members: &BTreeSet<usize>| members.iter().map(|i| data[*i].amount).collect::<Vec<_>>();
+
+    let mut columns = vec![rows.as_str().to_string()];
+    if let Some(c) = cols {
+        let mut taken = BTreeSet::from([rows.as_str().to_string(), TOTAL.to_string()]);
+        columns.extend(col_keys.iter().map(|k| unique_heading(&k.label, c, &mut taken)));
+    }
+    columns.push(TOTAL.to_string());
+    let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+
+    let mut title = match cols {
+        Some(c) => format!("{} of amounts by {} and {} for {}", value.as_str(), rows.as_str(), c.as_str(), range.label()),
+        None => format!("{} of amounts by {} for {}", value.as_str(), rows.as_str(), range.label()),
+    };
+    title[..1].make_ascii_uppercase();
+    let mut table = Table::new("pivot", &columns).with_title(title);
+
+    let mut taken = BTreeSet::from([TOTAL.to_string()]);
+    for row_key in &row_keys {
+        let mut row: Vec<Value> = vec![unique_heading(&row_key.label, rows, &mut taken).into()];
+        if cols.is_some() {
+            for col_key in &col_keys {
+                let cell = cells.get(&((*row_key).clone(), (*col_key).clone())).map_or(&[][..], Vec::as_slice);
+                row.push(value.apply(cell));
+            }
+        }
+        row.push(value.apply(&amounts(&row_members[*row_key])));
+        table.push(row);
+    }
+
+    let mut total: Vec<Value> = vec![TOTAL.into()];
+    if cols.is_some() {
+        total.extend(col_keys.iter().map(|k| value.apply(&amounts(&col_members[*k]))));
+    }
+    total.push(value.apply(&data.iter().map(|r| r.amount).collect::<Vec<_>>()));
+    table.push(total);
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(text: &str) -> (Option<Dimension>, Op, String) {
+        let filter = Filter::parse(text).unwrap();
+        (filter.field, filter.op, filter.value)
+    }
+
+    #[test]
+    fn two_character_operators_win() {
+        assert_eq!(parsed("amount<=-50"), (None, Op::Le, "-50".to_string()));
+        assert_eq!(parsed("amount>=10"), (None, Op::Ge, "10".to_string()));
+        assert_eq!(parsed("amount<0"), (None, Op::Lt, "0".to_string()));
+        assert_eq!(parsed("account!=Card"), (Some(Dimension::Account), Op::Ne, "Card".to_string()));
+    }
+
+    #[test]
+    fn the_first_operator_splits_name_from_value() {
+        assert_eq!(parsed("payee~a=b"), (Some(Dimension::Payee), Op::Contains, "a=b".to_string()));
+        assert_eq!(parsed(" Category = Food,Groceries "), (Some(Dimension::Category), Op::Eq, "Food,Groceries".to_string()));
+    }
+
+    #[test]
+    fn rejects_invalid_filters() {
+        for text in ["colour=red", "category<5", "amount=abc", "amount~5", "amount", "=Food"] {
+            assert!(Filter::parse(text).is_none(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use chrono::{Datelike, Days, Months, NaiveDate};
+use rusqlite::Connection;
+use crate::db::ISO_DATE;
+
+/// An inclusive range of days a report covers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl DateRange {
+    pub fn month(year: i32, month: u32) -> Option<Self> {
+        let from = NaiveDate::from_ymd_opt(year, month, 1)?;
+        Some(DateRange { from, to: from + Months::new(1) - Days::new(1) })
+    }
+
+    pub fn quarter(year: i32, quarter: u32) -> Option<Self> {
+        if !(1..=4).contains(&quarter) {
+            return None;
+        }
+        let from = NaiveDate::from_ymd_opt(year, (quarter - 1) * 3 + 1, 1)?;
+        Some(DateRange { from, to: from + Months::new(3) - Days::new(1) })
+    }
+
+    pub fn year(year: i32) -> Option<Self> {
+        Some(DateRange {
+            from: NaiveDate::from_ymd_opt(year, 1, 1)?,
+            to: NaiveDate::from_ymd_opt(year, 12, 31)?,
+        })
+    }
+
+    /// Parses a calendar period: `2026-03` or `03/2026`, `2026Q1`, or `2026`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Some(month) = crate::budget::envelope::parse_month(text) {
+            return DateRange::month(month.year(), month.month());
+        }
+        if let Some(quarter) = parse_quarter(text) {
+            return Some(quarter);
+        }
+        match text.parse() {
+            Ok(year) if text.len() == 4 => DateRange::year(year),
+            _ => None,
+        }
+    }
+
+    /// The calendar period of the same length `months` months earlier, e.g.
+    /// the previous quarter for `3`.
+    pub fn earlier(&self, months: u32) -> Self {
+        let from = self.from - Months::new(months);
+        DateRange { from, to: from + Months::new(self.months()) - Days::new(1) }
+    }
+
+    /// Length in whole calendar months.
+    pub fn months(&self) -> u32 {
+        let next = self.to + Days::new(1);
+        let months = (next.year() - self.from.year()) * 12 + next.month() as i32 - self.from.month() as i32;
+        months.max(0) as u32
+    }
+
+    /// A short name for titles: `10/2026`, `2026Q1`, `2026`, or the dates.
+    pub fn label(&self) -> String {
+        let (from, to) = (self.from, self.to);
+        if DateRange::month(from.year(), from.month()) == Some(*self) {
+            format!("{:02}/{}", from.month(), from.year())
+        } else if from.month() % 3 == 1 && DateRange::quarter(from.year(), from.month() / 3 + 1) == Some(*self) {
+            format!("{}Q{}", from.year(), from.month() / 3 + 1)
+        } else if DateRange::year(from.year()) == Some(*self) {
+            from.year().to_string()
+        } else {
+            format!("{} – {}", from, to)
+        }
+    }
+}
+
+
+/// The ways a report's range can be chosen on the command line. At most one
+/// of them is set; clap enforces the combinations.
+#[derive(Debug, Default)]
+pub struct RangeSpec {
+    pub month: Option<String>,
+    pub year: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub last: Option<String>,
+    pub ytd: bool,
+    pub quarter: Option<String>,
+}
+
+/// Resolves the requested range as of `today`. Without any option the
+/// report covers the current month.
+pub fn resolve(conn: &Connection, spec: &RangeSpec, today: NaiveDate) -> Result<DateRange, String> {
+    if let Some(last) = &spec.last {
+        return parse_last(last, today).ok_or_else(|| format!("Invalid period '{}'. Use e.g. 30d, 6w, 3m or 1y.", last));
+    }
+    if spec.ytd {
+        let from = NaiveDate::from_ymd_opt(today.year(), 1, 1).unwrap();
+        return Ok(DateRange { from, to: today });
+    }
+    if let Some(quarter) = &spec.quarter {
+        return parse_quarter(quarter).ok_or_else(|| format!("Invalid quarter '{}'. Use e.g. 2026Q1.", quarter));
+    }
+
+    if spec.from.is_some() || spec.to.is_some() {
+        let from = spec.from.as_deref().map(parse_date).transpose()?;
+        let to = spec.to.as_deref().map(parse_date).transpose()?.unwrap_or(today);
+        let from = match from {
+            Some(from) => from,
+            None => first_transaction(conn).map_or(to, |d| d.min(to)),
+        };
+        if from > to {
+            return Err(format!("--from {} is after --to {}.", from, to));
+        }
+        return Ok(DateRange { from, to });
+    }
+
+    let year = match &spec.year {
+        Some(y) => y.trim().parse().map_err(|_| format!("Invalid year '{}'.", y))?,
+        None => today.year(),
+    };
+    // A year on its own covers the whole year.
+    let month = match (&spec.month, &spec.year) {
+        (Some(m), _) => m.trim().parse().map_err(|_| format!("Invalid month '{}'.", m))?,
+        (None, Some(_)) => return DateRange::year(year).ok_or_else(|| format!("Invalid year '{}'.", year)),
+        (None, None) => today.month(),
+    };
+    DateRange::month(year, month).ok_or_else(|| format!("Invalid month '{}'.", month))
+}
+
+fn parse_date(text: &str) -> Result<NaiveDate, String> {
+    crate::parser::parse_date(text).ok_or_else(|| format!("Invalid date '{}'. Use MM/DD/YYYY or YYYY-MM-DD.", text))
+}
+
+// `3m` is the three months up to and including today.
+fn parse_last(text: &str, today: NaiveDate) -> Option<DateRange> {
+    let text = text.trim().to_ascii_lowercase();
+    let unit = text.chars().last()?;
+    let count: u32 = text[..text.len() - unit.len_utf8()].parse().ok().filter(|n| *n > 0)?;
+
+    let start = match unit {
+        'd' => today.checked_sub_days(Days::new(count as u64)),
+        'w' => today.checked_sub_days(Days::new(count as u64 * 7)),
+        'm' => today.checked_sub_months(Months::new(count)),
+        'y' => today.checked_sub_months(Months::new(count * 12)),
+        _ => None,
+    }?;
+    Some(DateRange { from: start + Days::new(1), to: today })
+}
+
+/// Parses `2026Q1` or `2026-Q1`.
+pub fn parse_quarter(text: &str) -> Option<DateRange> {
+    let text = text.trim().to_ascii_uppercase();
+    let (year, quarter) = text.split_once('Q')?;
+    DateRange::quarter(year.trim_end_matches('-').parse().ok()?, quarter.parse().ok()?)
+}
+
+fn first_transaction(conn: &Connection) -> Option<NaiveDate> {
+    let first: Option<String> = conn
+        .query_row(&format!("SELECT MIN({}) FROM transactions", ISO_DATE), [], |row| row.get(0))
+        .unwrap();
+    first.as_deref().and_then(crate::parser::parse_date)
+}
@@ -0,0 +1,108 @@
+use chrono::{Datelike, Days, Months, NaiveDate};
+use clap::ValueEnum;
+use rusqlite::Connection;
+use crate::output::Table;
+use super::range::DateRange;
+use super::{daily_category_totals, Totals};
+
+/// The bucket size of a time-series report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl GroupBy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupBy::Day => "day",
+            GroupBy::Week => "week",
+            GroupBy::Month => "month",
+            GroupBy::Quarter => "quarter",
+            GroupBy::Year => "year",
+        }
+    }
+
+    /// The first day of the bucket containing `date`. Weeks start on Monday.
+    pub fn bucket_start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            GroupBy::Day => date,
+            GroupBy::Week => date - Days::new(date.weekday().num_days_from_monday() as u64),
+            GroupBy::Month => date.with_day(1).unwrap(),
+            GroupBy::Quarter => NaiveDate::from_ymd_opt(date.year(), (date.month0() / 3) * 3 + 1, 1).unwrap(),
+            GroupBy::Year => NaiveDate::from_ymd_opt(date.year(), 1, 1).unwrap(),
+        }
+    }
+
+    fn next(&self, start: NaiveDate) -> NaiveDate {
+        match self {
+            GroupBy::Day => start + Days::new(1),
+            GroupBy::Week => start + Days::new(7),
+            GroupBy::Month => start + Months::new(1),
+            GroupBy::Quarter => start + Months::new(3),
+            GroupBy::Year => start + Months::new(12),
+        }
+    }
+
+    pub fn label(&self, start: NaiveDate) -> String {
+        match self {
+            GroupBy::Day => start.to_string(),
+            GroupBy::Week => {
+                let week = start.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            GroupBy::Month => start.format("%Y-%m").to_string(),
+            GroupBy::Quarter => format!("{}Q{}", start.year(), start.month0() / 3 + 1),
+            GroupBy::Year => start.year().to_string(),
+        }
+    }
+
+    /// Every bucket overlapping `range`, clipped to it, in order.
+    pub fn buckets(&self, range: DateRange) -> Vec<DateRange> {
+        let mut buckets = Vec::new();
+        let mut start = self.bucket_start(range.from);
+        while start <= range.to {
+            let next = self.next(start);
+            buckets.push(DateRange {
+                from: start.max(range.from),
+                to: (next - Days::new(1)).min(range.to),
+            });
+            start = next;
+        }
+        buckets
+    }
+}
+
+
+/// Income, expenses, transfers and net per bucket over `range`, including
+/// buckets without any transactions.
+pub fn time_series(conn: &Connection, range: DateRange, group_by: GroupBy) -> Table {
+    let mut table = Table::new("series", &["bucket", "from", "to", "income", "expenses", "transfers", "net"])
+        .with_title(format!("Income and expenses by {} for {}", group_by.as_str(), range.label()));
+
+    let buckets = group_by.buckets(range);
+    let mut totals = vec![Totals::default(); buckets.len()];
+    let mut i = 0;
+    for (date, category) in daily_category_totals(conn, range) {
+        while buckets[i].to < date {
+            i += 1;
+        }
+        totals[i].add(&category);
+    }
+
+    for (bucket, totals) in buckets.iter().zip(totals) {
+        table.push(vec![
+            group_by.label(group_by.bucket_start(bucket.from)).into(),
+            bucket.from.to_string().into(),
+            bucket.to.to_string().into(),
+            totals.income.into(),
+            totals.expenses.into(),
+            totals.transfers.into(),
+            totals.net().into(),
+        ]);
+    }
+    table
+}
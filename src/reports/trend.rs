@@ -0,0 +1,88 @@
+use chrono::{Months, NaiveDate};
+use rusqlite::Connection;
+use crate::output::Table;
+use super::range::DateRange;
+use super::series::GroupBy;
+use super::{category_lines, CategoryLine};
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// A one-line chart of `values`, scaled from zero (or the lowest negative
+/// value) to the highest.
+pub fn sparkline(values: &[f64]) -> String {
+    let low = values.iter().copied().fold(0.0, f64::min);
+    let high = values.iter().copied().fold(0.0, f64::max);
+    let span = high - low;
+
+    values
+        .iter()
+        .map(|v| {
+            if span <= 0.0 {
+                SPARKS[0]
+            } else {
+                SPARKS[(((v - low) / span) * (SPARKS.len() - 1) as f64).round() as usize]
+            }
+        })
+        .collect()
+}
+
+/// The `months` calendar months up to and including the one containing `today`.
+pub fn trend_months(months: u32, today: NaiveDate) -> Vec<DateRange> {
+    let current = GroupBy::Month.bucket_start(today);
+    let range = DateRange {
+        from: current - Months::new(months.max(1) - 1),
+        to: current + Months::new(1) - chrono::Days::new(1),
+    };
+    GroupBy::Month.buckets(range)
+}
+
+/// Category lines over `periods`, expenses first, each kind sorted by total.
+pub fn trend_lines(conn: &Connection, periods: &[DateRange]) -> Vec<CategoryLine> {
+    let mut lines = category_lines(conn, periods);
+    let order = |kind: &str| ["expense", "income", "transfer"].iter().position(|k| *k == kind);
+    lines.sort_by(|a, b| {
+        order(a.kind).cmp(&order(b.kind)).then_with(|| {
+            b.amounts.iter().sum::<f64>().total_cmp(&a.amounts.iter().sum::<f64>())
+        })
+    });
+    lines
+}
+
+/// A category × month matrix with totals, monthly averages and a sparkline
+/// per category, followed by total expense and income rows.
+pub fn trend(conn: &Connection, months: u32, today: NaiveDate) -> Table {
+    let periods = trend_months(months, today);
+    let lines = trend_lines(conn, &periods);
+
+    let labels: Vec<String> = periods.iter().map(|p| GroupBy::Month.label(p.from)).collect();
+    let mut columns = vec!["category", "type"];
+    columns.extend(labels.iter().map(String::as_str));
+    columns.extend(["total", "average", "trend"]);
+
+    let mut table = Table::new("trend", &columns).with_title(format!(
+        "Category trend for {} – {}",
+        labels.first().map_or("", String::as_str),
+        labels.last().map_or("", String::as_str)
+    ));
+
+    let mut push = |category: Option<String>, kind: &str, amounts: &[f64]| {
+        let total: f64 = amounts.iter().sum();
+        let mut row = vec![category.into(), kind.into()];
+        row.extend(amounts.iter().map(|a| (*a).into()));
+        row.push(total.into());
+        row.push((total / amounts.len() as f64).into());
+        row.push(sparkline(amounts).into());
+        table.push(row);
+    };
+
+    for line in &lines {
+        push(line.category.clone(), line.kind, &line.amounts);
+    }
+    for (kind, name) in [("expense", "Total expenses"), ("income", "Total income")] {
+        let totals: Vec<f64> = (0..periods.len())
+            .map(|i| lines.iter().filter(|l| l.kind == kind).map(|l| l.amounts[i]).sum())
+            .collect();
+        push(Some(name.to_string()), "total", &totals);
+    }
+    table
+}
@@ -1,14 +1,32 @@
+use chrono::{Local, NaiveDate};
+use crate::budget::envelope::{self, Envelope};
+use crate::reports::CategoryLine;
+use crate::reports::range::DateRange;
+
 #[derive(Debug, Clone, Copy)]
 pub enum AppScreen {
     MainMenu,
     Transactions,
     Budgets,
+    Envelopes,
     Reports,
 }
 
 pub struct App {
     pub screen: AppScreen,
     pub menu_index: usize,
+    pub envelope_month: NaiveDate,
+    pub envelopes: Vec<Envelope>,
+    pub envelope_index: usize,
+    pub to_be_budgeted: f64,
+    /// Amount being typed on the envelopes screen
+    pub input: String,
+    pub message: String,
+    /// Last month shown on the reports screen
+    pub report_month: NaiveDate,
+    /// The months of the reports screen's trend, oldest first
+    pub report_periods: Vec<DateRange>,
+    pub report_lines: Vec<CategoryLine>,
 }
 
 impl App {
@@ -16,6 +34,15 @@ impl App {
         Self {
             screen: AppScreen::MainMenu,
             menu_index: 0,
+            envelope_month: envelope::month_start(Local::now().date_naive()),
+            envelopes: Vec::new(),
+            envelope_index: 0,
+            to_be_budgeted: 0.0,
+            input: String::new(),
+            message: String::new(),
+            report_month: envelope::month_start(Local::now().date_naive()),
+            report_periods: Vec::new(),
+            report_lines: Vec::new(),
         }
     }
 }
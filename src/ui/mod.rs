@@ -1,11 +1,12 @@
 mod app;
 
 use crate::models::transaction::Transaction;
-use crate::models::budget::Budget;
-use crate::db::{load_transactions, load_budgets};
+use crate::db::load_transactions;
+use crate::reports;
+use crate::budget::{self, envelope, BudgetStatus, IncomeStatus, SavingsStatus};
+use crate::budget::status::BudgetState;
 use app::{App, AppScreen};
 use std::io;
-use std::collections::{HashMap, BTreeMap};
 use crossterm::{
     event::{self, Event, KeyCode, KeyEventKind},
     execute,
@@ -33,13 +34,15 @@ pub fn run_tui(conn: &Connection) -> io::Result<()> {
         }
     };
 
-    let budgets = match load_budgets(conn) {
+    let today = chrono::Local::now().date_naive();
+    let budgets = match budget::status::all_statuses(conn, today) {
         Ok(list) => list,
         Err(err) => {
-            println!("Failed to load budgets: {}", err);
+            println!("{}", err);
             Vec::new()
         }
     };
+    let (incomes, goals) = budget::status::target_statuses(conn, today).unwrap_or_default();
 
     let mut app = App::new();
 
@@ -48,43 +51,66 @@ pub fn run_tui(conn: &Connection) -> io::Result<()> {
             match app.screen {
                 AppScreen::MainMenu => draw_main_menu(f, &app),
                 AppScreen::Transactions => draw_transactions(f, &app, &transactions),
-                AppScreen::Budgets => draw_budgets(f, &app, &budgets, &transactions),
-                AppScreen::Reports => draw_reports_by_category(f, &app, &transactions),
+                AppScreen::Budgets => draw_budgets(f, &app, &budgets, &incomes, &goals),
+                AppScreen::Envelopes => draw_envelopes(f, &app),
+                AppScreen::Reports => draw_reports(f, &app),
             }
         })?;
 
-        if event::poll(std::time::Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind != KeyEventKind::Press {
-                    continue;
-                }
+        if event::poll(std::time::Duration::from_millis(50))?
+            && let Event::Key(key) = event::read()?
+        {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
 
-                match key.code {
-                    KeyCode::Char('q') => break,
-                    KeyCode::Char('m') => app.screen = AppScreen::MainMenu,
+            if let AppScreen::Envelopes = app.screen
+                && handle_envelope_key(conn, &mut app, key.code)
+            {
+                continue;
+            }
 
-                    KeyCode::Up => {
-                        if app.menu_index > 0 {
-                            app.menu_index -= 1;
-                        }
-                    }
+            if let AppScreen::Reports = app.screen
+                && matches!(key.code, KeyCode::Left | KeyCode::Right)
+            {
+                let months = chrono::Months::new(1);
+                app.report_month = if key.code == KeyCode::Left {
+                    app.report_month - months
+                } else {
+                    app.report_month + months
+                };
+                refresh_reports(conn, &mut app);
+                continue;
+            }
 
-                    KeyCode::Down => {
-                        if app.menu_index < 4 {
-                            app.menu_index += 1;
-                        }
-                    }
+            match key.code {
+                KeyCode::Char('q') => break,
+                KeyCode::Char('m') => app.screen = AppScreen::MainMenu,
 
-                    KeyCode::Enter => match app.menu_index {
-                        0 => app.screen = AppScreen::Transactions,
-                        1 => app.screen = AppScreen::Budgets,
-                        2 => app.screen = AppScreen::Reports,
-                        3 => break,
-                        _ => {}
-                    },
+                KeyCode::Up if app.menu_index > 0 => {
+                    app.menu_index -= 1;
+                }
 
-                    _ => {}
+                KeyCode::Down if app.menu_index < 4 => {
+                    app.menu_index += 1;
                 }
+
+                KeyCode::Enter => match app.menu_index {
+                    0 => app.screen = AppScreen::Transactions,
+                    1 => app.screen = AppScreen::Budgets,
+                    2 => {
+                        refresh_envelopes(conn, &mut app);
+                        app.screen = AppScreen::Envelopes;
+                    }
+                    3 => {
+                        refresh_reports(conn, &mut app);
+                        app.screen = AppScreen::Reports;
+                    }
+                    4 => break,
+                    _ => {}
+                },
+
+                _ => {}
             }
         }
     }
@@ -95,15 +121,16 @@ pub fn run_tui(conn: &Connection) -> io::Result<()> {
 }
 
 
-use ratatui::widgets::{List, ListItem, Block, Borders, Paragraph};
+use ratatui::widgets::{BarChart, List, ListItem, Block, Borders, Paragraph, Sparkline};
 use ratatui::layout::{Layout, Constraint, Direction};
 use ratatui::style::{Style, Color};
 
 
 fn draw_main_menu(f: &mut ratatui::Frame, app: &App) {
-    let menu_items = vec![
+    let menu_items = [
         "Transactions",
         "Budgets",
+        "Envelopes",
         "Reports",
         "Quit",
     ];
@@ -168,46 +195,66 @@ fn draw_transactions(
 fn draw_budgets(
     f: &mut ratatui::Frame,
     _app: &App,
-    budgets: &[Budget],
-    transactions: &[Transaction],
+    budgets: &[BudgetStatus],
+    incomes: &[IncomeStatus],
+    goals: &[SavingsStatus],
 ) {
-    let mut spent_per_category: HashMap<String, f64> = HashMap::new();
-
-    for t in transactions {
-        let cat = t
-            .category
-            .clone()
-            .unwrap_or_else(|| "Uncategorized".to_string());
-        if t.amount < 0.0 {
-            *spent_per_category.entry(cat).or_insert(0.0) += -t.amount; 
-        }
-    }
-
     let mut items: Vec<ListItem> = Vec::new();
 
     for b in budgets {
-        let spent = spent_per_category
-            .get(&b.category)
-            .cloned()
-            .unwrap_or(0.0);
-        let remaining = b.limit_amount - spent;
-        let percent = if b.limit_amount > 0.0 {
-            (spent / b.limit_amount * 100.0).min(999.9)
-        } else {
-            0.0
+        let percent = match b.percent {
+            Some(p) => format!("{:.1}%", p.min(999.9)),
+            None => "–".to_string(),
         };
-
         let text = format!(
-            "{}: limit {:.2} lei | spent {:.2} lei | remaining {:.2} lei ({:.1}%)",
-            b.category, b.limit_amount, spent, remaining, percent
+            "{} ({} {} – {}): limit {:.2} lei | spent {:.2} lei | refunds {:.2} lei | remaining {:.2} lei ({})",
+            b.category, b.period.as_str(), b.from, b.to, b.available(), b.spent, b.refunds, b.remaining(), percent
+        );
+        let pace_text = format!(
+            "    day {}/{} | expected by now {:.2} lei | projected {:.2} lei | safe to spend {:.2} lei/day",
+            b.pace.elapsed_days, b.pace.total_days, b.pace.expected_to_date, b.pace.projected, b.pace.safe_per_day
         );
 
-        let style = if remaining < 0.0 {
-            Style::default().fg(Color::Red)
+        let style = match b.state {
+            BudgetState::Exceeded => Style::default().fg(Color::Red),
+            _ => Style::default().fg(Color::White),
+        };
+        let pace_style = match b.state {
+            BudgetState::Warning(_) | BudgetState::OnPaceToOverspend(_) => Style::default().fg(Color::Yellow),
+            _ => Style::default().fg(Color::DarkGray),
+        };
+
+        items.push(ListItem::new(text).style(style));
+        items.push(ListItem::new(pace_text).style(pace_style));
+    }
+
+    for i in incomes {
+        let text = format!(
+            "{} income ({} {} – {}): expected {:.2} lei | received {:.2} lei | {}",
+            i.category, i.period.as_str(), i.from, i.to, i.expected, i.received, i.message()
+        );
+        let style = if i.shortfall() > 0.0 {
+            Style::default().fg(Color::Yellow)
         } else {
-            Style::default().fg(Color::White)
+            Style::default().fg(Color::Green)
         };
+        items.push(ListItem::new(text).style(style));
+    }
 
+    for g in goals {
+        let rate = match g.rate() {
+            Some(r) => format!("{:.1}%", r),
+            None => "–".to_string(),
+        };
+        let text = format!(
+            "{} ({} {} – {}): income {:.2} lei | expenses {:.2} lei | savings rate {} (target {:.1}%) | {}",
+            g.name, g.period.as_str(), g.from, g.to, g.income, g.expenses, rate, g.target, g.message()
+        );
+        let style = if g.rate().is_some_and(|r| r >= g.target) {
+            Style::default().fg(Color::Green)
+        } else {
+            Style::default().fg(Color::Yellow)
+        };
         items.push(ListItem::new(text).style(style));
     }
 
@@ -222,104 +269,222 @@ fn draw_budgets(
 }
 
 
-fn draw_reports_by_category(
-    f: &mut ratatui::Frame,
-    _app: &App,
-    transactions: &[Transaction],
-) {
-    use ratatui::widgets::{Block, Borders, Paragraph};
-    use ratatui::style::{Style, Color};
-    use chrono::Local;
-    use std::collections::BTreeMap;
+fn refresh_envelopes(conn: &Connection, app: &mut App) {
+    app.envelopes = envelope::envelopes(conn, app.envelope_month);
+    app.to_be_budgeted = envelope::to_be_budgeted(conn, app.envelope_month);
+    app.envelope_index = app.envelope_index.min(app.envelopes.len().saturating_sub(1));
+}
+
+/// Handles the keys specific to the envelopes screen. Returns `false` for
+/// keys that should fall through to the global bindings (`q`, `m`).
+fn handle_envelope_key(conn: &Connection, app: &mut App, code: KeyCode) -> bool {
+    match code {
+        KeyCode::Up => app.envelope_index = app.envelope_index.saturating_sub(1),
+        KeyCode::Down => {
+            if app.envelope_index + 1 < app.envelopes.len() {
+                app.envelope_index += 1;
+            }
+        }
+        KeyCode::Left | KeyCode::Right => {
+            let months = chrono::Months::new(1);
+            app.envelope_month = if code == KeyCode::Left {
+                app.envelope_month - months
+            } else {
+                app.envelope_month + months
+            };
+            refresh_envelopes(conn, app);
+        }
+        KeyCode::Char(c) if c.is_ascii_digit() || c == '.' || (c == '-' && app.input.is_empty()) => {
+            app.input.push(c);
+        }
+        KeyCode::Backspace => {
+            app.input.pop();
+        }
+        KeyCode::Esc => app.input.clear(),
+        KeyCode::Enter | KeyCode::Char('l') => {
+            let Some(selected) = app.envelopes.get(app.envelope_index) else {
+                return true;
+            };
+
+            // `l` tops the envelope up to its budget limit.
+            let amount = if code == KeyCode::Enter {
+                app.input.parse::<f64>().ok()
+            } else {
+                selected.limit.map(|limit| limit - selected.available)
+            };
+
+            match amount {
+                Some(amount) if amount != 0.0 => {
+                    let category = selected.category.clone();
+                    app.message = match envelope::assign(conn, &category, amount, app.envelope_month) {
+                        Ok(()) => format!("Assigned {:.2} lei to {}", amount, category),
+                        Err(err) => err,
+                    };
+                    refresh_envelopes(conn, app);
+                }
+                _ => app.message = "Nothing to assign.".to_string(),
+            }
+            app.input.clear();
+        }
+        _ => return false,
+    }
+    true
+}
+
 
+fn draw_envelopes(f: &mut ratatui::Frame, app: &App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(3),
             Constraint::Min(0),
+            Constraint::Length(3),
         ])
         .split(f.size());
 
-    let now = Local::now();
-    let month_name = now.format("%B").to_string();     // ex: "January"
-    let month_numeric = now.format("%m/%Y").to_string(); // ex: "01/2026"
-
-    let header = Block::default()
-        .title(format!(
-            " Reports by category — {} ({}) ",
-            month_name, month_numeric
-        ))
-        .borders(Borders::ALL)
-        .border_style(Style::default().fg(Color::Magenta));
+    let tbb_style = if app.to_be_budgeted < 0.0 {
+        Style::default().fg(Color::Red)
+    } else {
+        Style::default().fg(Color::Green)
+    };
+    let header = Paragraph::new(format!("To be budgeted: {:.2} lei", app.to_be_budgeted))
+        .style(tbb_style)
+        .block(
+            Block::default()
+                .title(format!(" Envelopes — {} (←/→ month) ", app.envelope_month.format("%Y-%m")))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Cyan))
+        );
     f.render_widget(header, chunks[0]);
 
-    let mut per_category: BTreeMap<String, f64> = BTreeMap::new();
-
-    for t in transactions {
-        if t.amount >= 0.0 {
-            continue;
-        }
-
-        if t.date.len() < 10 {
-            continue;
-        }
+    let items: Vec<ListItem> = app
+        .envelopes
+        .iter()
+        .enumerate()
+        .map(|(i, e)| {
+            let limit = e.limit.map(|l| format!(" | limit {:.2}", l)).unwrap_or_default();
+            let text = format!(
+                "{}{:<20} assigned {:>10.2} | activity {:>10.2} | available {:>10.2}{}",
+                if i == app.envelope_index { "> " } else { "  " },
+                e.category, e.assigned, e.activity, e.available, limit
+            );
 
-        let tx_month = format!("{}{}", &t.date[0..3], &t.date[6..10]); 
-        if tx_month != month_numeric {
-            continue; 
-        }
+            let style = if i == app.envelope_index {
+                Style::default().fg(Color::Yellow).bg(Color::Blue)
+            } else if e.available < 0.0 {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            ListItem::new(text).style(style)
+        })
+        .collect();
 
-        let cat = t
-            .category
-            .clone()
-            .unwrap_or_else(|| "Uncategorized".to_string());
+    let list = List::new(items)
+        .block(Block::default().title(" Envelopes ").borders(Borders::ALL));
+    f.render_widget(list, chunks[1]);
 
-        *per_category.entry(cat).or_insert(0.0) += -t.amount;
-    }
+    let prompt = Paragraph::new(format!("Amount: {}_   {}", app.input, app.message))
+        .block(
+            Block::default()
+                .title(" Type an amount + Enter to assign, l = fill to limit, Esc = clear ")
+                .borders(Borders::ALL)
+        );
+    f.render_widget(prompt, chunks[2]);
+}
 
-    if per_category.is_empty() {
-        let p = Paragraph::new("No expenses found for the current month.")
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title(" Category trends ")
-            )
-            .style(Style::default().fg(Color::White));
-        f.render_widget(p, chunks[1]);
-        return;
-    }
 
-    let mut full_text = String::new();
+// The reports screen shows a year of category trends ending at the
+// selected month.
+const REPORT_MONTHS: u32 = 12;
 
-    let max = per_category
-        .values()
-        .cloned()
-        .fold(f64::NEG_INFINITY, f64::max)
-        .max(1.0);
+fn refresh_reports(conn: &Connection, app: &mut App) {
+    app.report_periods = reports::trend::trend_months(REPORT_MONTHS, app.report_month);
+    app.report_lines = reports::trend::trend_lines(conn, &app.report_periods)
+        .into_iter()
+        .filter(|l| l.kind == "expense" && l.amounts.iter().any(|a| *a != 0.0))
+        .collect();
+}
 
-    let max_bar_width = 30.0;
+fn draw_reports(f: &mut ratatui::Frame, app: &App) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Percentage(40),
+            Constraint::Length(6),
+            Constraint::Min(0),
+        ])
+        .split(f.size());
 
-    for (cat, value) in &per_category {
-        let bar_len = ((value / max) * max_bar_width).round() as usize;
-        let bar = "█".repeat(bar_len.max(1));
+    let header = Block::default()
+        .title(format!(
+            " Reports by category — {} ({}) | ←/→ change month ",
+            app.report_month.format("%B"),
+            app.report_month.format("%m/%Y")
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Magenta));
+    f.render_widget(header, chunks[0]);
 
-        full_text.push_str(&format!(
-            "{}  {:<30}  {:.2} lei\n",
-            cat,
-            bar,
-            value
-        ));
+    if app.report_lines.is_empty() {
+        let p = Paragraph::new("No expenses found in the last 12 months.")
+            .block(Block::default().borders(Borders::ALL).title(" Category trends "))
+            .style(Style::default().fg(Color::White));
+        f.render_widget(p, chunks[1]);
+        return;
     }
 
-    full_text.push_str("----------------------------------------\n");
-
-    let chart = Paragraph::new(full_text)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title(" Monthly expenses by category ")
-        )
-        .style(Style::default().fg(Color::White));
-
+    // Expenses of the selected month, largest first.
+    let mut current: Vec<(String, u64)> = app
+        .report_lines
+        .iter()
+        .filter_map(|l| {
+            let amount = *l.amounts.last()?;
+            (amount > 0.0).then(|| (l.category.clone().unwrap_or_else(|| "Uncategorized".to_string()), amount.round() as u64))
+        })
+        .collect();
+    current.sort_by_key(|c| std::cmp::Reverse(c.1));
+    let bars: Vec<(&str, u64)> = current.iter().map(|(c, v)| (c.as_str(), *v)).collect();
+
+    let chart = BarChart::default()
+        .block(Block::default().borders(Borders::ALL).title(" Monthly expenses by category (lei) "))
+        .data(&bars)
+        .bar_width(9)
+        .bar_gap(2)
+        .bar_style(Style::default().fg(Color::Cyan))
+        .value_style(Style::default().fg(Color::Black).bg(Color::Cyan));
     f.render_widget(chart, chunks[1]);
+
+    let totals: Vec<u64> = (0..app.report_periods.len())
+        .map(|i| app.report_lines.iter().map(|l| l.amounts[i]).sum::<f64>().max(0.0).round() as u64)
+        .collect();
+    let first = app.report_periods.first().map(|p| p.from.format("%m/%Y").to_string()).unwrap_or_default();
+    let sparkline = Sparkline::default()
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            " Total expenses per month since {} ",
+            first
+        )))
+        .data(&totals)
+        .style(Style::default().fg(Color::Yellow));
+    f.render_widget(sparkline, chunks[2]);
+
+    let items: Vec<ListItem> = app
+        .report_lines
+        .iter()
+        .map(|l| {
+            let total: f64 = l.amounts.iter().sum();
+            ListItem::new(format!(
+                "{:<20} {}  {:>10.2} lei this month | {:>10.2} lei avg",
+                l.category.as_deref().unwrap_or("Uncategorized"),
+                reports::trend::sparkline(&l.amounts),
+                l.amounts.last().copied().unwrap_or(0.0),
+                total / l.amounts.len() as f64
+            ))
+            .style(Style::default().fg(Color::White))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(" Category trends "));
+    f.render_widget(list, chunks[3]);
 }